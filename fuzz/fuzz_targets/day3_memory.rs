@@ -0,0 +1,14 @@
+#![no_main]
+#![allow(dead_code)]
+#![allow(unused_imports)]
+
+use libfuzzer_sys::fuzz_target;
+
+#[path = "../../src/day3.rs"]
+mod day3;
+#[path = "../../src/rng.rs"]
+mod rng;
+
+fuzz_target!(|data: &[u8]| {
+    day3::fuzz_memory(data);
+});
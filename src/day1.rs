@@ -3,44 +3,117 @@ use std::{
     str::Lines,
 };
 
-pub(crate) fn day_1_1() {
-    let lines: Lines = include_str!("../data/day1.txt").lines();
+/// Column-oriented location lists, one `Vec` per input column
+#[derive(Debug)]
+struct Lists {
+    columns: Vec<Vec<i32>>,
+}
 
-    let mut left: Vec<i32> = vec![];
-    let mut right: Vec<i32> = vec![];
+impl Lists {
+    /// Parses whitespace separated columns, one list per column
+    ///
+    /// Panics if a row does not have as many columns as the first one
+    fn from_lines(lines: Lines) -> Self {
+        let mut columns: Vec<Vec<i32>> = vec![];
 
-    lines.for_each(|line| {
-        left.push(line.split_at(5).0.parse::<i32>().unwrap());
-        right.push(line.split_at(5).1.trim().parse::<i32>().unwrap());
-    });
+        lines
+            .enumerate()
+            .filter(|(_, line)| !line.trim().is_empty())
+            .for_each(|(row, line)| {
+                let values: Vec<i32> = line
+                    .split_whitespace()
+                    .map(|value| value.parse::<i32>().unwrap())
+                    .collect();
 
-    left.sort_unstable();
-    right.sort_unstable();
+                if columns.is_empty() {
+                    columns = vec![vec![]; values.len()];
+                } else if values.len() != columns.len() {
+                    panic!(
+                        "Ragged row : line {} has {} columns, expected {}",
+                        row + 1,
+                        values.len(),
+                        columns.len()
+                    );
+                }
 
-    let mut total_distance = 0;
+                for (column, value) in columns.iter_mut().zip(values) {
+                    column.push(value);
+                }
+            });
+
+        Self { columns }
+    }
 
-    for i in 0..left.len() {
-        let distance = max(left[i], right[i]) - min(left[i], right[i]);
-        total_distance += distance;
+    /// Returns the number of lists
+    fn len(&self) -> usize {
+        self.columns.len()
     }
 
+    /// Sums the distances between the two lists once both are sorted
+    fn distance(&self, a: usize, b: usize) -> i32 {
+        let mut left = self.columns[a].clone();
+        let mut right = self.columns[b].clone();
+
+        left.sort_unstable();
+        right.sort_unstable();
+
+        left.iter()
+            .zip(right.iter())
+            .map(|(&l, &r)| max(l, r) - min(l, r))
+            .sum()
+    }
+
+    /// Sums each value of list `a` multiplied by its number of occurrences in list `b`
+    fn similarity(&self, a: usize, b: usize) -> i32 {
+        self.columns[a].iter().fold(0, |acc, i| {
+            acc + (i32::try_from(self.columns[b].iter().filter(|j| i == *j).count()).unwrap() * i)
+        })
+    }
+
+    /// Builds the N×N matrix of pairwise sorted distances
+    fn distance_matrix(&self) -> Vec<Vec<i32>> {
+        (0..self.len())
+            .map(|a| (0..self.len()).map(|b| self.distance(a, b)).collect())
+            .collect()
+    }
+
+    /// Builds the N×N matrix of pairwise similarity scores (row list scored against column list)
+    fn similarity_matrix(&self) -> Vec<Vec<i32>> {
+        (0..self.len())
+            .map(|a| (0..self.len()).map(|b| self.similarity(a, b)).collect())
+            .collect()
+    }
+}
+
+/// Prints a square matrix with one row per line
+fn print_matrix(name: &str, matrix: &[Vec<i32>]) {
+    println!("{name} :");
+
+    for row in matrix {
+        let row: Vec<String> = row.iter().map(ToString::to_string).collect();
+        println!("{}", row.join("\t"));
+    }
+}
+
+pub(crate) fn day_1_1() {
+    let lists = Lists::from_lines(include_str!("../data/day1.txt").lines());
+
+    let total_distance = lists.distance(0, 1);
+
     println!("Total distance = {total_distance}");
 }
 
 pub(crate) fn day_1_2() {
-    let lines: Lines = include_str!("../data/day1.txt").lines();
+    let lists = Lists::from_lines(include_str!("../data/day1.txt").lines());
 
-    let mut left: Vec<i32> = vec![];
-    let mut right: Vec<i32> = vec![];
+    let similarity = lists.similarity(0, 1);
 
-    lines.for_each(|line| {
-        left.push(line.split_at(5).0.parse::<i32>().unwrap());
-        right.push(line.split_at(5).1.trim().parse::<i32>().unwrap());
-    });
+    println!("Similarity = {similarity}");
+}
 
-    let similarity = left.iter().fold(0, |acc, i| {
-        acc + (i32::try_from(right.iter().filter(|j| i == *j).count()).unwrap() * i)
-    });
+pub(crate) fn day_1_matrices() {
+    let lists = Lists::from_lines(include_str!("../data/day1.txt").lines());
 
-    println!("Similarity = {similarity}");
+    print_matrix("Distances", &lists.distance_matrix());
+    print_matrix("Similarities", &lists.similarity_matrix());
 }
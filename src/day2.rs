@@ -1,5 +1,300 @@
 use std::cmp::max;
 use std::cmp::min;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::io::{self, BufRead, BufWriter, Write};
+use std::ops::Range;
+
+/// Directions a report is allowed to follow
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Direction {
+    Increasing,
+    Decreasing,
+    Any,
+}
+
+impl Direction {
+    /// Creates direction from its CLI representation
+    fn from(s: &str) -> Self {
+        match s {
+            "increasing" | "inc" => Direction::Increasing,
+            "decreasing" | "dec" => Direction::Decreasing,
+            "any" => Direction::Any,
+            _ => panic!("Invalid direction : {s}"),
+        }
+    }
+
+    /// Checks if increasing reports are allowed
+    fn allows_increasing(self) -> bool {
+        matches!(self, Direction::Increasing | Direction::Any)
+    }
+
+    /// Checks if decreasing reports are allowed
+    fn allows_decreasing(self) -> bool {
+        matches!(self, Direction::Decreasing | Direction::Any)
+    }
+}
+
+/// Rules deciding whether a report is safe
+#[derive(Debug, Clone, Copy)]
+struct SafetyPolicy {
+    min_delta: i32,
+    max_delta: i32,
+    strict: bool,
+    direction: Direction,
+}
+
+impl Default for SafetyPolicy {
+    /// The puzzle rules: deltas within 1..=3, non-strict monotonicity in either direction
+    fn default() -> Self {
+        Self {
+            min_delta: 1,
+            max_delta: 3,
+            strict: false,
+            direction: Direction::Any,
+        }
+    }
+}
+
+impl SafetyPolicy {
+    /// Builds a policy from command line flags, falling back to the default rules
+    /// Recognised flags: `--min-delta N`, `--max-delta N`, `--strict`, `--direction increasing|decreasing|any`
+    fn from_args(mut args: impl Iterator<Item = String>) -> Self {
+        let mut policy = Self::default();
+
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--min-delta" => {
+                    policy.min_delta = args.next().unwrap().parse::<i32>().unwrap();
+                }
+                "--max-delta" => {
+                    policy.max_delta = args.next().unwrap().parse::<i32>().unwrap();
+                }
+                "--strict" => policy.strict = true,
+                "--direction" => policy.direction = Direction::from(&args.next().unwrap()),
+                _ => (),
+            }
+        }
+
+        policy
+    }
+
+    /// Checks if the gap between two consecutive levels is within bounds
+    fn delta_is_safe(&self, a: i32, b: i32) -> bool {
+        let delta = max(a, b) - min(a, b);
+        delta >= self.min_delta && delta <= self.max_delta
+    }
+
+    /// Checks if two consecutive levels go up
+    fn step_increases(&self, a: i32, b: i32) -> bool {
+        if self.strict {
+            a < b
+        } else {
+            a <= b
+        }
+    }
+
+    /// Checks if two consecutive levels go down
+    fn step_decreases(&self, a: i32, b: i32) -> bool {
+        if self.strict {
+            a > b
+        } else {
+            a >= b
+        }
+    }
+}
+
+/// Rule broken by a window of two consecutive levels
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Violation {
+    Direction,
+    Delta,
+}
+
+/// First offending window of a report, `window` being the index of its first level
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Failure {
+    window: usize,
+    violation: Violation,
+}
+
+/// Structured outcome of checking a report
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Verdict {
+    Safe,
+    Dampened { failure: Failure, removed: usize },
+    Unsafe { failure: Failure },
+}
+
+impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Violation::Direction => write!(f, "direction change"),
+            Violation::Delta => write!(f, "delta out of bounds"),
+        }
+    }
+}
+
+impl fmt::Display for Failure {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} at window {}", self.violation, self.window)
+    }
+}
+
+impl fmt::Display for Verdict {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Verdict::Safe => write!(f, "safe"),
+            Verdict::Dampened { failure, removed } => {
+                write!(f, "dampen safe, {failure}, passes without index {removed}")
+            }
+            Verdict::Unsafe { failure } => write!(f, "unsafe, {failure}"),
+        }
+    }
+}
+
+/// Running totals of a streaming classification
+#[derive(Debug, Default)]
+struct Totals {
+    reports: usize,
+    safe: usize,
+    dampen_safe: usize,
+}
+
+/// Number of windows of a given length that are safe or dampen safe
+#[derive(Debug, Default, PartialEq, Eq)]
+struct WindowCounts {
+    safe: usize,
+    dampen_safe: usize,
+}
+
+/// Validity of every step of a series for one direction, precomputed so that any window
+/// can be checked in constant time instead of being re-scanned
+/// Step `i` goes from level `i` to level `i + 1`
+struct Steps<'a> {
+    levels: &'a [i32],
+    policy: &'a SafetyPolicy,
+    direction: Direction,
+    bad_before: Vec<usize>,
+    next_bad: Vec<usize>,
+}
+
+impl<'a> Steps<'a> {
+    /// Profiles the steps of `levels` for `direction`, which must be increasing or decreasing
+    fn new(levels: &'a [i32], policy: &'a SafetyPolicy, direction: Direction) -> Self {
+        let mut steps = Self {
+            levels,
+            policy,
+            direction,
+            bad_before: vec![0],
+            next_bad: vec![],
+        };
+
+        let count = levels.len().saturating_sub(1);
+
+        for i in 0..count {
+            let bad = !steps.is_good(levels[i], levels[i + 1]);
+            steps
+                .bad_before
+                .push(steps.bad_before[i] + usize::from(bad));
+        }
+
+        steps.next_bad = vec![count; count + 1];
+
+        for i in (0..count).rev() {
+            steps.next_bad[i] = if steps.bad_before[i + 1] > steps.bad_before[i] {
+                i
+            } else {
+                steps.next_bad[i + 1]
+            };
+        }
+
+        steps
+    }
+
+    /// Checks if going from `a` to `b` follows the direction and delta rules
+    fn is_good(&self, a: i32, b: i32) -> bool {
+        let direction_ok = match self.direction {
+            Direction::Increasing => self.policy.step_increases(a, b),
+            _ => self.policy.step_decreases(a, b),
+        };
+
+        direction_ok && self.policy.delta_is_safe(a, b)
+    }
+
+    /// Counts bad steps among `steps`
+    fn bad_in(&self, steps: Range<usize>) -> usize {
+        if steps.is_empty() {
+            0
+        } else {
+            self.bad_before[steps.end] - self.bad_before[steps.start]
+        }
+    }
+
+    /// Checks if the levels in `window` are safe
+    fn is_safe(&self, window: &Range<usize>) -> bool {
+        self.bad_in(window.start..window.end - 1) == 0
+    }
+
+    /// Checks if the levels in `window` are safe once at most one of them is removed
+    /// Removing level `r` drops steps `r - 1` and `r`, so every bad step must be one of
+    /// those two, which leaves the two levels around the first bad step as candidates
+    fn is_dampen_safe(&self, window: &Range<usize>) -> bool {
+        let steps = window.start..window.end - 1;
+
+        match self.bad_in(steps.clone()) {
+            0 => true,
+            1 | 2 => {
+                let first = self.next_bad[steps.start];
+
+                [first, first + 1]
+                    .into_iter()
+                    .any(|removed| self.is_safe_without(window, removed))
+            }
+            _ => false,
+        }
+    }
+
+    /// Checks if the levels in `window` are safe without level `removed`
+    fn is_safe_without(&self, window: &Range<usize>, removed: usize) -> bool {
+        let last = window.end - 1;
+
+        if removed == window.start {
+            return self.bad_in(window.start + 1..last) == 0;
+        }
+
+        if removed == last {
+            return self.bad_in(window.start..last - 1) == 0;
+        }
+
+        removed > window.start
+            && removed < last
+            && self.bad_in(window.start..removed - 1) == 0
+            && self.bad_in(removed + 1..last) == 0
+            && self.is_good(self.levels[removed - 1], self.levels[removed + 1])
+    }
+
+    /// Returns the maximal windows made only of good steps, at least two levels long
+    fn runs(&self) -> Vec<Range<usize>> {
+        let mut runs = vec![];
+        let mut start = 0;
+
+        for i in 0..self.bad_before.len() - 1 {
+            if self.bad_in(i..i + 1) == 1 {
+                if i > start {
+                    runs.push(start..i + 1);
+                }
+                start = i + 1;
+            }
+        }
+
+        if self.bad_before.len() - 1 > start {
+            runs.push(start..self.levels.len());
+        }
+
+        runs
+    }
+}
 
 #[derive(Debug, Clone)]
 struct Report {
@@ -11,43 +306,269 @@ impl Report {
         Report { levels }
     }
 
-    fn is_safe(&self) -> bool {
-        (self.is_increasing() || self.is_decreasing()) && self.deltas_are_safe()
+    fn is_safe(&self, policy: &SafetyPolicy) -> bool {
+        (self.is_increasing(policy) || self.is_decreasing(policy)) && self.deltas_are_safe(policy)
     }
 
-    fn is_dampen_safe(&self) -> bool {
-        self.is_safe() || self.can_dampen()
+    fn is_dampen_safe(&self, policy: &SafetyPolicy) -> bool {
+        self.can_dampen(policy, 1)
     }
 
-    fn deltas_are_safe(&self) -> bool {
-        self.levels.windows(2).all(|l| {
-            (max(l[0], l[1]) - min(l[0], l[1]) >= 1) && (max(l[0], l[1]) - min(l[0], l[1]) <= 3)
-        })
+    fn deltas_are_safe(&self, policy: &SafetyPolicy) -> bool {
+        self.levels
+            .windows(2)
+            .all(|l| policy.delta_is_safe(l[0], l[1]))
+    }
+
+    fn is_increasing(&self, policy: &SafetyPolicy) -> bool {
+        policy.direction.allows_increasing()
+            && self
+                .levels
+                .windows(2)
+                .all(|l| policy.step_increases(l[0], l[1]))
+    }
+
+    fn is_decreasing(&self, policy: &SafetyPolicy) -> bool {
+        policy.direction.allows_decreasing()
+            && self
+                .levels
+                .windows(2)
+                .all(|l| policy.step_decreases(l[0], l[1]))
+    }
+
+    /// Explains why the report is unsafe and, if a single removal fixes it, which one
+    fn explain(&self, policy: &SafetyPolicy) -> Verdict {
+        let Some(failure) = self.first_failure(policy) else {
+            return Verdict::Safe;
+        };
+
+        match self.removals(policy, 1).as_deref() {
+            Some(&[removed]) => Verdict::Dampened { failure, removed },
+            _ => Verdict::Unsafe { failure },
+        }
+    }
+
+    /// Finds the first window breaking the policy
+    /// With `Direction::Any`, the report direction is set by the first window that only
+    /// goes one way, a direction violation taking precedence over a delta one
+    fn first_failure(&self, policy: &SafetyPolicy) -> Option<Failure> {
+        let mut direction = match policy.direction {
+            Direction::Any => None,
+            direction => Some(direction),
+        };
+
+        for (window, l) in self.levels.windows(2).enumerate() {
+            let increases = policy.step_increases(l[0], l[1]);
+            let decreases = policy.step_decreases(l[0], l[1]);
+
+            let direction_ok = match direction {
+                Some(Direction::Increasing) => increases,
+                Some(Direction::Decreasing) => decreases,
+                _ => {
+                    if increases && !decreases {
+                        direction = Some(Direction::Increasing);
+                    } else if decreases && !increases {
+                        direction = Some(Direction::Decreasing);
+                    }
+
+                    increases || decreases
+                }
+            };
+
+            let violation = if !direction_ok {
+                Violation::Direction
+            } else if !policy.delta_is_safe(l[0], l[1]) {
+                Violation::Delta
+            } else {
+                continue;
+            };
+
+            return Some(Failure { window, violation });
+        }
+
+        None
+    }
+
+    /// Profiles the steps of the report for every direction allowed by the policy
+    fn steps<'a>(&'a self, policy: &'a SafetyPolicy) -> Vec<Steps<'a>> {
+        [Direction::Increasing, Direction::Decreasing]
+            .into_iter()
+            .filter(|&direction| match direction {
+                Direction::Increasing => policy.direction.allows_increasing(),
+                _ => policy.direction.allows_decreasing(),
+            })
+            .map(|direction| Steps::new(&self.levels, policy, direction))
+            .collect()
+    }
+
+    /// Finds every maximal safe window of the series, as ranges of level indices
+    /// A level with no good step on either side is a safe window of its own
+    fn maximal_safe_windows(&self, policy: &SafetyPolicy) -> Vec<Range<usize>> {
+        let mut windows: Vec<Range<usize>> =
+            self.steps(policy).iter().flat_map(Steps::runs).collect();
+
+        let mut covered = vec![false; self.levels.len()];
+        for window in &windows {
+            covered[window.clone()].iter_mut().for_each(|c| *c = true);
+        }
+
+        windows.extend(
+            (0..self.levels.len())
+                .filter(|&i| !covered[i])
+                .map(|i| i..i + 1),
+        );
+
+        // Runs of both directions can nest when equal levels are allowed
+        windows.sort_by_key(|w| (w.start, std::cmp::Reverse(w.end)));
+
+        let mut maximal: Vec<Range<usize>> = vec![];
+        for window in windows {
+            if maximal.last().is_none_or(|last| window.end > last.end) {
+                maximal.push(window);
+            }
+        }
+
+        maximal
+    }
+
+    /// Finds the longest safe window of the series, the earliest one on ties
+    fn longest_safe_window(&self, policy: &SafetyPolicy) -> Option<Range<usize>> {
+        self.maximal_safe_windows(policy)
+            .into_iter()
+            .rev()
+            .max_by_key(ExactSizeIterator::len)
     }
 
-    fn is_increasing(&self) -> bool {
-        self.levels.windows(2).all(|l| l[0] <= l[1])
+    /// Counts the windows of `len` levels that are safe or dampen safe, in O(n) overall
+    fn count_windows(&self, policy: &SafetyPolicy, len: usize) -> WindowCounts {
+        let mut counts = WindowCounts::default();
+
+        if len == 0 || len > self.levels.len() {
+            return counts;
+        }
+
+        let steps = self.steps(policy);
+
+        for start in 0..=self.levels.len() - len {
+            let window = start..start + len;
+
+            if steps.iter().any(|s| s.is_safe(&window)) {
+                counts.safe += 1;
+                counts.dampen_safe += 1;
+            } else if steps.iter().any(|s| s.is_dampen_safe(&window)) {
+                counts.dampen_safe += 1;
+            }
+        }
+
+        counts
+    }
+
+    /// Checks if the report can be made safe by removing at most `k` levels
+    fn can_dampen(&self, policy: &SafetyPolicy, k: usize) -> bool {
+        self.removals(policy, k).is_some()
     }
 
-    fn is_decreasing(&self) -> bool {
-        self.levels.windows(2).all(|l| l[0] >= l[1])
+    /// Returns the indices of the fewest levels to delete to make the report safe
+    fn min_removals(&self, policy: &SafetyPolicy) -> Vec<usize> {
+        self.removals(policy, self.levels.len())
+            .expect("keeping a single level is always safe")
     }
 
-    fn can_dampen(&self) -> bool {
-        for (i, _) in self.levels.iter().enumerate() {
-            let mut candidate = self.clone();
-            candidate.levels.remove(i);
+    /// Finds the smallest set of at most `k` levels to delete to make the report safe,
+    /// trying every allowed direction
+    fn removals(&self, policy: &SafetyPolicy, k: usize) -> Option<Vec<usize>> {
+        let increasing = policy
+            .direction
+            .allows_increasing()
+            .then(|| {
+                self.removals_following(k, |a, b| {
+                    policy.step_increases(a, b) && policy.delta_is_safe(a, b)
+                })
+            })
+            .flatten();
+
+        let decreasing = policy
+            .direction
+            .allows_decreasing()
+            .then(|| {
+                self.removals_following(k, |a, b| {
+                    policy.step_decreases(a, b) && policy.delta_is_safe(a, b)
+                })
+            })
+            .flatten();
 
-            if candidate.is_safe() {
-                return true;
+        match (increasing, decreasing) {
+            (Some(inc), Some(dec)) => Some(if dec.len() < inc.len() { dec } else { inc }),
+            (inc, dec) => inc.or(dec),
+        }
+    }
+
+    /// Finds the smallest set of at most `k` levels to delete so that every remaining
+    /// consecutive pair satisfies `step`, or `None` if more than `k` are needed
+    /// This is a longest valid subsequence search: `best[i]` holds the fewest removals
+    /// before `i` when `i` is kept, and only the `k + 1` previous levels can precede it,
+    /// so this runs in O(n·k)
+    fn removals_following(&self, k: usize, step: impl Fn(i32, i32) -> bool) -> Option<Vec<usize>> {
+        let n = self.levels.len();
+
+        if n == 0 {
+            return Some(vec![]);
+        }
+
+        let mut best: Vec<Option<usize>> = vec![None; n];
+        let mut previous: Vec<Option<usize>> = vec![None; n];
+
+        for i in 0..n {
+            // Dropping every level before `i`
+            let mut current = (i <= k).then_some(i);
+
+            for (j, before) in best
+                .iter()
+                .enumerate()
+                .take(i)
+                .skip(i.saturating_sub(k + 1))
+            {
+                let Some(before) = *before else {
+                    continue;
+                };
+
+                let removed = before + (i - j - 1);
+
+                if removed <= k
+                    && current.is_none_or(|c| removed < c)
+                    && step(self.levels[j], self.levels[i])
+                {
+                    current = Some(removed);
+                    previous[i] = Some(j);
+                }
             }
+
+            best[i] = current;
+        }
+
+        // Dropping every level after the last kept one
+        let (last, _) = best
+            .iter()
+            .enumerate()
+            .filter_map(|(i, before)| before.map(|b| (i, b + (n - 1 - i))))
+            .filter(|&(_, removed)| removed <= k)
+            .min_by_key(|&(_, removed)| removed)?;
+
+        let mut kept = vec![false; n];
+        let mut cursor = Some(last);
+
+        while let Some(i) = cursor {
+            kept[i] = true;
+            cursor = previous[i];
         }
 
-        false
+        Some((0..n).filter(|&i| !kept[i]).collect())
     }
 }
 
 pub(crate) fn day_2_1() {
+    let policy = SafetyPolicy::from_args(std::env::args().skip(1));
+
     let reports: Vec<Report> = include_str!("../data/day2.txt")
         .lines()
         .map(|line| {
@@ -59,12 +580,17 @@ pub(crate) fn day_2_1() {
         })
         .collect();
 
-    let res = reports.iter().filter(|report| report.is_safe()).count();
+    let res = reports
+        .iter()
+        .filter(|report| report.is_safe(&policy))
+        .count();
 
     println!("Number of safe reports = {res}");
 }
 
 pub(crate) fn day_2_2() {
+    let policy = SafetyPolicy::from_args(std::env::args().skip(1));
+
     let reports: Vec<Report> = include_str!("../data/day2.txt")
         .lines()
         .map(|line| {
@@ -76,10 +602,133 @@ pub(crate) fn day_2_2() {
         })
         .collect();
 
+    if std::env::args().any(|arg| arg == "--explain") {
+        for (line, report) in include_str!("../data/day2.txt").lines().zip(&reports) {
+            println!("{line} : {}", report.explain(&policy));
+        }
+    }
+
     let res = reports
         .iter()
-        .filter(|report| report.is_dampen_safe())
+        .filter(|report| report.is_dampen_safe(&policy))
         .count();
 
     println!("Number of dampen safe reports = {res}");
 }
+
+pub(crate) fn day_2_removals() {
+    let policy = SafetyPolicy::from_args(std::env::args().skip(1));
+
+    let reports: Vec<Report> = include_str!("../data/day2.txt")
+        .lines()
+        .map(|line| {
+            Report::new(
+                line.split_whitespace()
+                    .map(|s| s.parse::<i32>().unwrap())
+                    .collect(),
+            )
+        })
+        .collect();
+
+    let mut distribution: BTreeMap<usize, usize> = BTreeMap::new();
+
+    for report in &reports {
+        *distribution
+            .entry(report.min_removals(&policy).len())
+            .or_default() += 1;
+    }
+
+    for (removals, count) in distribution {
+        println!("{removals} removal(s) needed : {count} reports");
+    }
+}
+
+/// Classifies reports read line by line, writing a `line,safe,dampen_safe` CSV row per report
+/// Only the current line is held in memory, blank lines are skipped but still numbered
+fn classify_stream(
+    reader: impl BufRead,
+    mut writer: impl Write,
+    policy: &SafetyPolicy,
+) -> io::Result<Totals> {
+    let mut totals = Totals::default();
+
+    writeln!(writer, "line,safe,dampen_safe")?;
+
+    for (index, line) in reader.lines().enumerate() {
+        let line = line?;
+
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let report = Report::new(
+            line.split_whitespace()
+                .map(|s| {
+                    s.parse::<i32>()
+                        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+                })
+                .collect::<io::Result<Vec<i32>>>()?,
+        );
+
+        let safe = report.is_safe(policy);
+        let dampen_safe = safe || report.is_dampen_safe(policy);
+
+        totals.reports += 1;
+        totals.safe += usize::from(safe);
+        totals.dampen_safe += usize::from(dampen_safe);
+
+        writeln!(writer, "{},{safe},{dampen_safe}", index + 1)?;
+    }
+
+    writer.flush()?;
+
+    Ok(totals)
+}
+
+pub(crate) fn day_2_stream() {
+    let policy = SafetyPolicy::from_args(std::env::args().skip(1));
+
+    let totals = classify_stream(
+        io::stdin().lock(),
+        BufWriter::new(io::stdout().lock()),
+        &policy,
+    )
+    .expect("Failed to classify reports");
+
+    eprintln!(
+        "Reports = {}, safe = {}, dampen safe = {}",
+        totals.reports, totals.safe, totals.dampen_safe
+    );
+}
+
+pub(crate) fn day_2_windows() {
+    let policy = SafetyPolicy::from_args(std::env::args().skip(1));
+    let len = std::env::args()
+        .skip_while(|arg| arg != "--window")
+        .nth(1)
+        .map_or(5, |s| s.parse::<usize>().unwrap());
+
+    // The whole input is read as one continuous series
+    let series = Report::new(
+        include_str!("../data/day2.txt")
+            .split_whitespace()
+            .map(|s| s.parse::<i32>().unwrap())
+            .collect(),
+    );
+
+    let windows = series.maximal_safe_windows(&policy);
+    let longest = series.longest_safe_window(&policy).unwrap_or_default();
+    let counts = series.count_windows(&policy, len);
+
+    println!("Maximal safe windows = {}", windows.len());
+    println!(
+        "Longest safe run = {} levels ({}..{})",
+        longest.len(),
+        longest.start,
+        longest.end
+    );
+    println!(
+        "Windows of {len} levels : safe = {}, dampen safe = {}",
+        counts.safe, counts.dampen_safe
+    );
+}
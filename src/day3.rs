@@ -1,5 +1,746 @@
+#[cfg(fuzzing)]
+use crate::rng::Rng;
 use regex::Regex;
+use std::fmt;
+use std::io::{self, Read};
+use std::ops::Range;
 use std::str::Lines;
+use std::time::{Duration, Instant};
+
+/// Size of the buffer the scanner reads memory into
+const CHUNK_SIZE: usize = 4096;
+
+/// ANSI colours used to highlight memory
+const ENABLED_COLOUR: &str = "\x1b[32m";
+const DISABLED_COLOUR: &str = "\x1b[33m";
+const GARBAGE_COLOUR: &str = "\x1b[90m";
+const RESET_COLOUR: &str = "\x1b[0m";
+
+/// Instructions recognised in corrupted memory
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Instruction {
+    Mul(i64, i64),
+    Do,
+    Dont,
+}
+
+/// Scanner states, named after the bytes matched so far
+#[derive(Debug, Clone, Copy)]
+enum State {
+    Start,
+    M,
+    Mu,
+    Mul,
+    First { value: i64, digits: u8 },
+    Second { first: i64, value: i64, digits: u8 },
+    D,
+    Do,
+    DoOpen,
+    Don,
+    DonQuote,
+    DonQuoteT,
+    DontOpen,
+}
+
+/// Outcome of feeding one byte to the scanner
+enum Step {
+    Continue(State),
+    Emit(Instruction),
+    Mismatch,
+}
+
+/// State machine recognising `mul(a,b)`, `do()` and `don't()` one byte at a time,
+/// with the same leftmost non-overlapping semantics as the regex
+/// The state survives between calls, so tokens split across chunks are still found
+#[derive(Debug)]
+struct Scanner {
+    state: State,
+}
+
+impl Scanner {
+    /// Creates a scanner waiting for the start of an instruction
+    fn new() -> Self {
+        Self {
+            state: State::Start,
+        }
+    }
+
+    /// Feeds the next byte, returning the instruction it completes if any
+    fn feed(&mut self, byte: u8) -> Option<Instruction> {
+        match self.step(byte) {
+            Step::Continue(state) => self.state = state,
+            Step::Emit(instruction) => {
+                self.state = State::Start;
+                return Some(instruction);
+            }
+            // No instruction starts inside a partial match, so the byte is only
+            // re-examined as a potential start
+            Step::Mismatch => {
+                self.state = State::Start;
+                if let Step::Continue(state) = self.step(byte) {
+                    self.state = state;
+                }
+            }
+        }
+
+        None
+    }
+
+    /// Computes the transition for the given byte from the current state
+    fn step(&self, byte: u8) -> Step {
+        let digit = byte.is_ascii_digit().then(|| i64::from(byte - b'0'));
+
+        match (self.state, byte, digit) {
+            (State::Start, b'm', _) => Step::Continue(State::M),
+            (State::Start, b'd', _) => Step::Continue(State::D),
+            (State::Start, _, _) => Step::Continue(State::Start),
+            (State::M, b'u', _) => Step::Continue(State::Mu),
+            (State::Mu, b'l', _) => Step::Continue(State::Mul),
+            (State::Mul, b'(', _) => Step::Continue(State::First {
+                value: 0,
+                digits: 0,
+            }),
+            (State::First { value, digits }, _, Some(d)) if digits < 3 => {
+                Step::Continue(State::First {
+                    value: value * 10 + d,
+                    digits: digits + 1,
+                })
+            }
+            (State::First { value, digits }, b',', _) if digits > 0 => {
+                Step::Continue(State::Second {
+                    first: value,
+                    value: 0,
+                    digits: 0,
+                })
+            }
+            (
+                State::Second {
+                    first,
+                    value,
+                    digits,
+                },
+                _,
+                Some(d),
+            ) if digits < 3 => Step::Continue(State::Second {
+                first,
+                value: value * 10 + d,
+                digits: digits + 1,
+            }),
+            (
+                State::Second {
+                    first,
+                    value,
+                    digits,
+                },
+                b')',
+                _,
+            ) if digits > 0 => Step::Emit(Instruction::Mul(first, value)),
+            (State::D, b'o', _) => Step::Continue(State::Do),
+            (State::Do, b'(', _) => Step::Continue(State::DoOpen),
+            (State::Do, b'n', _) => Step::Continue(State::Don),
+            (State::DoOpen, b')', _) => Step::Emit(Instruction::Do),
+            (State::Don, b'\'', _) => Step::Continue(State::DonQuote),
+            (State::DonQuote, b't', _) => Step::Continue(State::DonQuoteT),
+            (State::DonQuoteT, b'(', _) => Step::Continue(State::DontOpen),
+            (State::DontOpen, b')', _) => Step::Emit(Instruction::Dont),
+            _ => Step::Mismatch,
+        }
+    }
+
+    /// Scans a reader chunk by chunk, calling `on_instruction` for every instruction found
+    fn scan(
+        &mut self,
+        mut reader: impl Read,
+        mut on_instruction: impl FnMut(Instruction),
+    ) -> io::Result<()> {
+        let mut buffer = [0u8; CHUNK_SIZE];
+
+        loop {
+            let read = match reader.read(&mut buffer) {
+                Ok(0) => return Ok(()),
+                Ok(read) => read,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e),
+            };
+
+            for &byte in &buffer[..read] {
+                if let Some(instruction) = self.feed(byte) {
+                    on_instruction(instruction);
+                }
+            }
+        }
+    }
+}
+
+/// Error returned when the total of a program, or a result added to it, does not fit in
+/// an `i64`
+#[derive(Debug, PartialEq, Eq)]
+struct OverflowError;
+
+impl fmt::Display for OverflowError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "total overflows i64")
+    }
+}
+
+/// Running state of a program: whether `mul` is enabled, the sum so far and whether it
+/// overflowed since the last reset
+#[derive(Debug)]
+struct Machine {
+    enabled: bool,
+    total: i64,
+    overflowed: bool,
+}
+
+impl Machine {
+    /// Creates a machine with `mul` enabled and a zero total
+    fn new() -> Self {
+        Self {
+            enabled: true,
+            total: 0,
+            overflowed: false,
+        }
+    }
+
+    /// Adds a checked result to the total, recording an overflow and leaving the total
+    /// unchanged if anything overflowed
+    fn accumulate(&mut self, value: Option<i64>) {
+        match value.and_then(|value| self.total.checked_add(value)) {
+            Some(total) => self.total = total,
+            None => self.overflowed = true,
+        }
+    }
+
+    /// Returns the total, or an error if it overflowed
+    fn result(&self) -> Result<i64, OverflowError> {
+        if self.overflowed {
+            Err(OverflowError)
+        } else {
+            Ok(self.total)
+        }
+    }
+}
+
+/// Adds the product of both operands to the total if enabled
+fn mul(machine: &mut Machine, operands: &[i64]) {
+    if machine.enabled {
+        machine.accumulate(operands[0].checked_mul(operands[1]));
+    }
+}
+
+/// Enables the following instructions
+fn enable(machine: &mut Machine, _: &[i64]) {
+    machine.enabled = true;
+}
+
+/// Disables the following instructions
+fn disable(machine: &mut Machine, _: &[i64]) {
+    machine.enabled = false;
+}
+
+/// Adds the sum of both operands to the total if enabled
+fn add(machine: &mut Machine, operands: &[i64]) {
+    if machine.enabled {
+        machine.accumulate(operands[0].checked_add(operands[1]));
+    }
+}
+
+/// Subtracts the second operand from the first and adds the result to the total if enabled
+fn sub(machine: &mut Machine, operands: &[i64]) {
+    if machine.enabled {
+        machine.accumulate(operands[0].checked_sub(operands[1]));
+    }
+}
+
+/// Resets the total to zero, forgetting any earlier overflow
+fn reset(machine: &mut Machine, _: &[i64]) {
+    machine.total = 0;
+    machine.overflowed = false;
+}
+
+/// Options of the memory parser
+#[derive(Debug, Clone, Copy)]
+struct ParserOptions {
+    max_digits: usize,
+    signed: bool,
+    whitespace: bool,
+}
+
+impl Default for ParserOptions {
+    /// The puzzle rules: unsigned operands of up to 3 digits, no whitespace
+    fn default() -> Self {
+        Self {
+            max_digits: 3,
+            signed: false,
+            whitespace: false,
+        }
+    }
+}
+
+impl ParserOptions {
+    /// Largest digit limit whose operands always fit in an `i64`
+    const MAX_DIGITS: usize = 18;
+
+    /// Builds options from command line flags, falling back to the default rules
+    /// Recognised flags: `--max-digits N` (1 to 18), `--signed`, `--whitespace`
+    fn from_args(mut args: impl Iterator<Item = String>) -> Self {
+        let mut options = Self::default();
+
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--max-digits" => {
+                    let max_digits = args.next().unwrap().parse::<usize>().unwrap();
+
+                    if !(1..=Self::MAX_DIGITS).contains(&max_digits) {
+                        panic!(
+                            "Invalid max digits : {max_digits}, expected 1 to {}",
+                            Self::MAX_DIGITS
+                        );
+                    }
+
+                    options.max_digits = max_digits;
+                }
+                "--signed" => options.signed = true,
+                "--whitespace" => options.whitespace = true,
+                _ => (),
+            }
+        }
+
+        options
+    }
+
+    /// Returns the operand rule these options describe
+    fn operand(self) -> Operand {
+        Operand {
+            max_digits: self.max_digits,
+            signed: self.signed,
+        }
+    }
+}
+
+/// Rule an operand must follow to be recognised: one up to `max_digits` digits, with an
+/// optional leading `-` if `signed`
+/// Operands are parsed as `i64`, so `max_digits` is kept between 1 and 18
+#[derive(Debug, Clone, Copy)]
+struct Operand {
+    max_digits: usize,
+    signed: bool,
+}
+
+impl Operand {
+    /// Returns the regex pattern matching the operand, without capture group
+    /// Only ASCII digits are accepted, as `\d` would also match other Unicode digits
+    fn pattern(self) -> String {
+        let sign = if self.signed { "-?" } else { "" };
+        format!(r"{sign}[0-9]{{1,{}}}", self.max_digits)
+    }
+}
+
+/// Definition of an instruction: its name, the rule of each operand and its effect
+#[derive(Debug, Clone)]
+struct InstructionDef {
+    name: &'static str,
+    operands: Vec<Operand>,
+    effect: fn(&mut Machine, &[i64]),
+}
+
+impl InstructionDef {
+    /// Returns the number of operands
+    fn arity(&self) -> usize {
+        self.operands.len()
+    }
+}
+
+/// A recognised instruction: the index of its definition in the set, its operands and
+/// the byte span it covers in memory
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Call {
+    instruction: usize,
+    operands: Vec<i64>,
+    span: Range<usize>,
+}
+
+/// Audit entry of a call: where it sits, whether it was enabled when reached and how much
+/// it changed the total
+#[derive(Debug)]
+struct Listing {
+    call: Call,
+    line: usize,
+    column: usize,
+    enabled: bool,
+    contribution: i64,
+}
+
+/// Registry of the instructions an interpreter recognises, in priority order
+/// If `whitespace` is set, blanks are tolerated around the operands
+#[derive(Debug, Clone)]
+struct InstructionSet {
+    defs: Vec<InstructionDef>,
+    whitespace: bool,
+}
+
+impl Default for InstructionSet {
+    /// The puzzle instructions: `mul(a,b)`, `do()` and `don't()`
+    fn default() -> Self {
+        Self::with_options(ParserOptions::default())
+    }
+}
+
+impl InstructionSet {
+    /// Creates an empty instruction set
+    fn new() -> Self {
+        Self {
+            defs: vec![],
+            whitespace: false,
+        }
+    }
+
+    /// Creates the puzzle instructions following the given parser options
+    fn with_options(options: ParserOptions) -> Self {
+        let operand = options.operand();
+
+        Self::new()
+            .whitespace(options.whitespace)
+            .register("mul", &[operand, operand], mul)
+            .register("do", &[], enable)
+            .register("don't", &[], disable)
+    }
+
+    /// Sets whether blanks are tolerated around operands and returns the set
+    fn whitespace(mut self, whitespace: bool) -> Self {
+        self.whitespace = whitespace;
+        self
+    }
+
+    /// Registers an instruction and returns the set
+    fn register(
+        mut self,
+        name: &'static str,
+        operands: &[Operand],
+        effect: fn(&mut Machine, &[i64]),
+    ) -> Self {
+        self.defs.push(InstructionDef {
+            name,
+            operands: operands.to_vec(),
+            effect,
+        });
+        self
+    }
+
+    /// Returns the definition registered under `name`, if any
+    fn get(&self, name: &str) -> Option<&InstructionDef> {
+        self.defs.iter().find(|def| def.name == name)
+    }
+
+    /// Builds a regex matching any instruction of the set, along with the index of the
+    /// group wrapping each instruction, its operands being the groups right after it
+    fn regex(&self) -> (Regex, Vec<usize>) {
+        let mut alternatives = vec![];
+        let mut groups = vec![];
+        let mut group = 1;
+        let blank = if self.whitespace { r"\s*" } else { "" };
+
+        for def in &self.defs {
+            let operands: Vec<String> = def
+                .operands
+                .iter()
+                .map(|operand| format!("({})", operand.pattern()))
+                .collect();
+
+            alternatives.push(format!(
+                r"({}\({blank}{}{blank}\))",
+                regex::escape(def.name),
+                operands.join(&format!("{blank},{blank}"))
+            ));
+            groups.push(group);
+            group += 1 + def.arity();
+        }
+
+        (Regex::new(&alternatives.join("|")).unwrap(), groups)
+    }
+
+    /// Extracts every instruction of the set found in a memory dump, leftmost first
+    fn parse(&self, memory: &str) -> Vec<Call> {
+        let (re, groups) = self.regex();
+
+        self.calls(&re, &groups, memory).collect()
+    }
+
+    /// Lazily extracts the instructions found in a memory dump with a regex built by
+    /// `regex`, so that it can be compiled once for several dumps
+    /// Calls with an operand that does not fit in an `i64` are skipped
+    fn calls<'a>(
+        &'a self,
+        re: &'a Regex,
+        groups: &'a [usize],
+        memory: &'a str,
+    ) -> impl Iterator<Item = Call> + 'a {
+        re.captures_iter(memory).filter_map(move |cap| {
+            let Some(instruction) = groups.iter().position(|&g| cap.get(g).is_some()) else {
+                panic!("no match found");
+            };
+
+            let operands = (0..self.defs[instruction].arity())
+                .map(|i| {
+                    cap.get(groups[instruction] + 1 + i)
+                        .unwrap()
+                        .as_str()
+                        .parse::<i64>()
+                        .ok()
+                })
+                .collect::<Option<Vec<i64>>>()?;
+
+            Some(Call {
+                instruction,
+                operands,
+                span: cap.get(0).unwrap().range(),
+            })
+        })
+    }
+
+    /// Applies one call to the machine
+    fn apply(&self, machine: &mut Machine, call: &Call) {
+        (self.defs[call.instruction].effect)(machine, &call.operands);
+    }
+
+    /// Interprets a memory dump, recording for every call its position (1-based line and
+    /// column), the enabled state before it and its contribution to the total
+    /// Returns an error if the total, or the change a call makes to it, overflows
+    fn listing(&self, memory: &str) -> Result<Vec<Listing>, OverflowError> {
+        let line_starts: Vec<usize> = std::iter::once(0)
+            .chain(memory.match_indices('\n').map(|(i, _)| i + 1))
+            .collect();
+
+        let mut machine = Machine::new();
+        let mut listing = vec![];
+
+        for call in self.parse(memory) {
+            let line = line_starts.partition_point(|&start| start <= call.span.start);
+            let column = memory[line_starts[line - 1]..call.span.start]
+                .chars()
+                .count()
+                + 1;
+            let enabled = machine.enabled;
+            let before = machine.total;
+
+            self.apply(&mut machine, &call);
+            machine.result()?;
+
+            // A reset from a large total changes it by more than an `i64` holds
+            let contribution = machine.total.checked_sub(before).ok_or(OverflowError)?;
+
+            listing.push(Listing {
+                contribution,
+                call,
+                line,
+                column,
+                enabled,
+            });
+        }
+
+        Ok(listing)
+    }
+
+    /// Rebuilds a clean program holding only the recognised instructions, one per line
+    fn sanitize(&self, memory: &str) -> String {
+        self.parse(memory)
+            .iter()
+            .map(|call| &memory[call.span.clone()])
+            .collect::<Vec<&str>>()
+            .join("\n")
+    }
+
+    /// Returns the memory with enabled instructions, disabled instructions and garbage in
+    /// different colours, an instruction being enabled if `mul` was when it was reached
+    fn highlight(&self, memory: &str) -> String {
+        let mut machine = Machine::new();
+        let mut highlighted = String::new();
+        let mut cursor = 0;
+
+        for call in self.parse(memory) {
+            let colour = if machine.enabled {
+                ENABLED_COLOUR
+            } else {
+                DISABLED_COLOUR
+            };
+
+            highlighted.push_str(GARBAGE_COLOUR);
+            highlighted.push_str(&memory[cursor..call.span.start]);
+            highlighted.push_str(colour);
+            highlighted.push_str(&memory[call.span.clone()]);
+
+            self.apply(&mut machine, &call);
+            cursor = call.span.end;
+        }
+
+        highlighted.push_str(GARBAGE_COLOUR);
+        highlighted.push_str(&memory[cursor..]);
+        highlighted.push_str(RESET_COLOUR);
+
+        highlighted
+    }
+
+    /// Interprets every instruction of a memory dump and returns the total, or an error if
+    /// it overflowed
+    fn run(&self, memory: &str) -> Result<i64, OverflowError> {
+        let (re, groups) = self.regex();
+
+        self.run_with(&re, &groups, memory)
+    }
+
+    /// Interprets every instruction of a memory dump with a regex built by `regex` and
+    /// returns the total, or an error if it overflowed
+    fn run_with(&self, re: &Regex, groups: &[usize], memory: &str) -> Result<i64, OverflowError> {
+        let mut machine = Machine::new();
+
+        for call in self.calls(re, groups, memory) {
+            self.apply(&mut machine, &call);
+        }
+
+        machine.result()
+    }
+}
+
+/// Scans a memory dump from any reader without loading it whole and returns the total
+/// The scanner only knows the default instructions, those missing from `set` are ignored
+/// An overflow of the total is reported as an `InvalidData` error
+fn scan_total(reader: impl Read, set: &InstructionSet) -> io::Result<i64> {
+    let mut machine = Machine::new();
+
+    Scanner::new().scan(reader, |instruction| {
+        let (name, operands) = match instruction {
+            Instruction::Mul(op1, op2) => ("mul", [op1, op2]),
+            Instruction::Do => ("do", [0, 0]),
+            Instruction::Dont => ("don't", [0, 0]),
+        };
+
+        if let Some(def) = set.get(name) {
+            (def.effect)(&mut machine, &operands[..def.arity()]);
+        }
+    })?;
+
+    machine
+        .result()
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e.to_string()))
+}
+
+/// Reader handing out its data in chunks of a fixed size, to split tokens at will
+#[cfg(fuzzing)]
+struct Chunked<'a> {
+    data: &'a [u8],
+    size: usize,
+}
+
+#[cfg(fuzzing)]
+impl Read for Chunked<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let len = self.size.min(buf.len()).min(self.data.len());
+
+        buf[..len].copy_from_slice(&self.data[..len]);
+        self.data = &self.data[len..];
+
+        Ok(len)
+    }
+}
+
+/// Reference implementation of the default instructions, trying each one literally at
+/// every position like the regex does
+#[cfg(fuzzing)]
+fn reference_instructions(memory: &[u8]) -> Vec<Instruction> {
+    let mut instructions = vec![];
+    let mut i = 0;
+
+    while i < memory.len() {
+        let rest = &memory[i..];
+
+        let found = if let Some((op1, op2, len)) = reference_mul(rest) {
+            Some((Instruction::Mul(op1, op2), len))
+        } else if rest.starts_with(b"do()") {
+            Some((Instruction::Do, 4))
+        } else if rest.starts_with(b"don't()") {
+            Some((Instruction::Dont, 7))
+        } else {
+            None
+        };
+
+        if let Some((instruction, len)) = found {
+            instructions.push(instruction);
+            i += len;
+        } else {
+            i += 1;
+        }
+    }
+
+    instructions
+}
+
+/// Matches `mul(a,b)` at the start of `memory`, returning its operands and length
+#[cfg(fuzzing)]
+fn reference_mul(memory: &[u8]) -> Option<(i64, i64, usize)> {
+    let rest = memory.strip_prefix(b"mul(")?;
+    let (op1, len1) = reference_number(rest)?;
+    let rest = rest[len1..].strip_prefix(b",")?;
+    let (op2, len2) = reference_number(rest)?;
+
+    rest[len2..]
+        .starts_with(b")")
+        .then_some((op1, op2, 4 + len1 + 1 + len2 + 1))
+}
+
+/// Matches a run of one to three ASCII digits at the start of `memory`
+#[cfg(fuzzing)]
+fn reference_number(memory: &[u8]) -> Option<(i64, usize)> {
+    let len = memory.iter().take_while(|b| b.is_ascii_digit()).count();
+
+    (1..=3).contains(&len).then(|| {
+        let value = memory[..len]
+            .iter()
+            .fold(0, |acc, b| acc * 10 + i64::from(b - b'0'));
+        (value, len)
+    })
+}
+
+/// Fuzzing entry point: scans `data` in chunks sized after its first byte and checks the
+/// scanner, and the regex parser on UTF-8 input, agree with the reference implementation
+#[cfg(fuzzing)]
+pub(crate) fn fuzz_memory(data: &[u8]) {
+    let Some((&size, memory)) = data.split_first() else {
+        return;
+    };
+
+    let expected = reference_instructions(memory);
+
+    let mut scanned = vec![];
+    Scanner::new()
+        .scan(
+            Chunked {
+                data: memory,
+                size: usize::from(size).max(1),
+            },
+            |instruction| scanned.push(instruction),
+        )
+        .unwrap();
+
+    assert_eq!(scanned, expected, "Scanner disagrees with the reference");
+
+    if let Ok(memory) = std::str::from_utf8(memory) {
+        let set = InstructionSet::default();
+        let parsed: Vec<Instruction> = set
+            .parse(memory)
+            .into_iter()
+            .map(|call| match set.defs[call.instruction].name {
+                "mul" => Instruction::Mul(call.operands[0], call.operands[1]),
+                "do" => Instruction::Do,
+                _ => Instruction::Dont,
+            })
+            .collect();
+
+        assert_eq!(
+            parsed, expected,
+            "Regex parser disagrees with the reference"
+        );
+    }
+}
 
 pub(crate) fn day_3_1() {
     let line = include_str!("../data/day3.txt")
@@ -7,16 +748,16 @@ pub(crate) fn day_3_1() {
         .collect::<Vec<&str>>()
         .join("\n");
 
-    let re = Regex::new(r"mul\((\d{1,3}),(\d{1,3})\)").unwrap();
-    let mut mul = vec![];
+    let options = ParserOptions::from_args(std::env::args().skip(1));
+    let operand = options.operand();
 
-    for (_, [op1, op2]) in re.captures_iter(&line).map(|c| c.extract()) {
-        mul.push((op1.parse::<i32>().unwrap(), op2.parse::<i32>().unwrap()));
+    let set = InstructionSet::new()
+        .whitespace(options.whitespace)
+        .register("mul", &[operand, operand], mul);
+    match set.run(&line) {
+        Ok(res) => println!("Total = {res}"),
+        Err(e) => println!("No total : {e}"),
     }
-
-    let res = mul.iter().fold(0, |acc, (op1, op2)| acc + (op1 * op2));
-
-    println!("Total = {res}");
 }
 
 pub(crate) fn day_3_2() {
@@ -25,31 +766,133 @@ pub(crate) fn day_3_2() {
         .collect::<Vec<&str>>()
         .join("\n");
 
-    let re = Regex::new(r"mul\((\d{1,3}),(\d{1,3})\)|do\(\)|don't\(\)").unwrap();
-    let mut capture = true;
-    let mut mul = vec![];
+    let options = ParserOptions::from_args(std::env::args().skip(1));
+    match InstructionSet::with_options(options).run(&line) {
+        Ok(res) => println!("Total = {res}"),
+        Err(e) => println!("No total : {e}"),
+    }
+}
 
-    for cap in re.captures_iter(&line) {
-        let Some(m) = cap.get(0) else {
-            panic!("no match found");
-        };
+pub(crate) fn day_3_listing() {
+    let memory = include_str!("../data/day3.txt");
+    let options = ParserOptions::from_args(std::env::args().skip(1));
+    let set = InstructionSet::with_options(options);
 
-        match m.as_str() {
-            "do()" => capture = true,
-            "don't()" => capture = false,
-            _ if capture => {
-                if let (Some(op1), Some(op2)) = (cap.get(1), cap.get(2)) {
-                    mul.push((
-                        op1.as_str().parse::<i32>().unwrap(),
-                        op2.as_str().parse::<i32>().unwrap(),
-                    ));
-                }
-            }
-            _ => (),
+    let listing = match set.listing(memory) {
+        Ok(listing) => listing,
+        Err(e) => {
+            println!("No listing : {e}");
+            return;
         }
+    };
+
+    for entry in listing {
+        let operands: Vec<String> = entry.call.operands.iter().map(i64::to_string).collect();
+
+        println!(
+            "{}..{} {}:{} {}({}) {} {:+}",
+            entry.call.span.start,
+            entry.call.span.end,
+            entry.line,
+            entry.column,
+            set.defs[entry.call.instruction].name,
+            operands.join(","),
+            if entry.enabled { "enabled" } else { "disabled" },
+            entry.contribution
+        );
     }
+}
+
+pub(crate) fn day_3_clean() {
+    let memory = include_str!("../data/day3.txt");
+    let options = ParserOptions::from_args(std::env::args().skip(1));
+    let set = InstructionSet::with_options(options);
 
-    let res = mul.iter().fold(0, |acc, (op1, op2)| acc + (op1 * op2));
+    if std::env::args().any(|arg| arg == "--highlight") {
+        println!("{}", set.highlight(memory));
+    } else {
+        println!("{}", set.sanitize(memory));
+    }
+}
+
+pub(crate) fn day_3_extended() {
+    let line = include_str!("../data/day3.txt")
+        .lines()
+        .collect::<Vec<&str>>()
+        .join("\n");
+
+    let options = ParserOptions::from_args(std::env::args().skip(1));
+    let operand = options.operand();
+
+    let set = InstructionSet::with_options(options)
+        .register("add", &[operand, operand], add)
+        .register("sub", &[operand, operand], sub)
+        .register("reset", &[], reset);
+
+    match set.run(&line) {
+        Ok(res) => println!("Total = {res}"),
+        Err(e) => println!("No total : {e}"),
+    }
+}
+
+pub(crate) fn day_3_scan() {
+    let memory = include_bytes!("../data/day3.txt");
+    // The scanner only recognises the default operands
+    let operand = ParserOptions::default().operand();
+    let mul_only = InstructionSet::new().register("mul", &[operand, operand], mul);
+
+    let total = scan_total(&memory[..], &mul_only).expect("Failed to scan memory");
+    let conditional_total =
+        scan_total(&memory[..], &InstructionSet::default()).expect("Failed to scan memory");
+
+    println!("Total = {total}");
+    println!("Conditional total = {conditional_total}");
+}
+
+pub(crate) fn day_3_bench() {
+    const RUNS: u32 = 20;
+
+    let memory = include_str!("../data/day3.txt");
+    let set = InstructionSet::default();
+    // Compiled once, so that only the tokenizers themselves are timed
+    let (re, groups) = set.regex();
+
+    let mut regex_time = Duration::ZERO;
+    let mut scan_time = Duration::ZERO;
+
+    for _ in 0..RUNS {
+        let start = Instant::now();
+        let regex_res = set.run_with(&re, &groups, memory).ok();
+        regex_time += start.elapsed();
+
+        let start = Instant::now();
+        let scan_res = scan_total(memory.as_bytes(), &set).ok();
+        scan_time += start.elapsed();
+
+        assert_eq!(regex_res, scan_res, "Scanner and regex disagree");
+    }
+
+    println!("Regex   : {:?} per run", regex_time / RUNS);
+    println!("Scanner : {:?} per run", scan_time / RUNS);
+}
+
+/// Checks random inputs the way the fuzz target does, without libFuzzer
+/// Only built for fuzzing, e.g. with `RUSTFLAGS="--cfg fuzzing" cargo run`
+#[cfg(fuzzing)]
+pub(crate) fn day_3_fuzz() {
+    const RUNS: usize = 100_000;
+    const ALPHABET: &[u8] = b"mul(),0123456789don't() \n\xc3\xa9";
+
+    let mut rng = Rng::new(0x2024_0003);
+
+    for _ in 0..RUNS {
+        let len = rng.below(64);
+        let data: Vec<u8> = (0..len)
+            .map(|_| ALPHABET[rng.below(ALPHABET.len())])
+            .collect();
+
+        fuzz_memory(&data);
+    }
 
-    println!("Total = {res}");
+    println!("{RUNS} inputs checked");
 }
@@ -1,16 +1,50 @@
 #![allow(clippy::cast_possible_wrap)]
 #![allow(clippy::cast_sign_loss)]
 
+use crate::rng::Rng;
+use std::collections::HashMap;
+use std::fmt;
+use std::ops::Range;
 use std::str::Lines;
+use std::thread;
+use std::time::Instant;
 
 #[derive(Debug)]
 struct Grid {
     width: isize,
     height: isize,
     cells: Vec<char>,
+    toroidal: bool,
 }
 
-#[derive(Copy, Clone)]
+/// Reasons a grid cannot be parsed
+#[derive(Debug, PartialEq, Eq)]
+enum GridError {
+    Empty,
+    RaggedRow {
+        line: usize,
+        expected: usize,
+        found: usize,
+    },
+}
+
+impl fmt::Display for GridError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            GridError::Empty => write!(f, "no rows"),
+            GridError::RaggedRow {
+                line,
+                expected,
+                found,
+            } => write!(
+                f,
+                "line {line} has {found} letters, expected {expected} like the first line"
+            ),
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
 struct Position {
     x: isize,
     y: isize,
@@ -20,6 +54,148 @@ impl Position {
     fn new(x: isize, y: isize) -> Self {
         Position { x, y }
     }
+
+    fn step(self, direction: Direction) -> Self {
+        Position::new(self.x + direction.dx, self.y + direction.dy)
+    }
+}
+
+/// Step between two consecutive letters of a word
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+struct Direction {
+    dx: isize,
+    dy: isize,
+}
+
+impl Direction {
+    const UP: Direction = Direction { dx: 0, dy: -1 };
+    const UP_RIGHT: Direction = Direction { dx: 1, dy: -1 };
+    const RIGHT: Direction = Direction { dx: 1, dy: 0 };
+    const DOWN_RIGHT: Direction = Direction { dx: 1, dy: 1 };
+    const DOWN: Direction = Direction { dx: 0, dy: 1 };
+    const DOWN_LEFT: Direction = Direction { dx: -1, dy: 1 };
+    const LEFT: Direction = Direction { dx: -1, dy: 0 };
+    const UP_LEFT: Direction = Direction { dx: -1, dy: -1 };
+
+    /// No movement, the direction of single letter matches
+    const NONE: Direction = Direction { dx: 0, dy: 0 };
+
+    /// The eight straight directions, clockwise from up
+    const ALL: [Direction; 8] = [
+        Direction::UP,
+        Direction::UP_RIGHT,
+        Direction::RIGHT,
+        Direction::DOWN_RIGHT,
+        Direction::DOWN,
+        Direction::DOWN_LEFT,
+        Direction::LEFT,
+        Direction::UP_LEFT,
+    ];
+
+    /// The eight knight jumps, clockwise from up
+    const KNIGHT: [Direction; 8] = [
+        Direction { dx: 1, dy: -2 },
+        Direction { dx: 2, dy: -1 },
+        Direction { dx: 2, dy: 1 },
+        Direction { dx: 1, dy: 2 },
+        Direction { dx: -1, dy: 2 },
+        Direction { dx: -2, dy: 1 },
+        Direction { dx: -2, dy: -1 },
+        Direction { dx: -1, dy: -2 },
+    ];
+
+    fn new(dx: isize, dy: isize) -> Self {
+        Direction { dx, dy }
+    }
+
+    /// Parses a step written as `dx,dy`
+    fn parse(s: &str) -> Self {
+        let Some((dx, dy)) = s.split_once(',') else {
+            panic!("Invalid step : {s}");
+        };
+
+        Direction::new(
+            dx.trim().parse::<isize>().unwrap(),
+            dy.trim().parse::<isize>().unwrap(),
+        )
+    }
+}
+
+/// Occurrence of a word: its index in the searched list, its first letter, the direction
+/// it is read in and its length in letters
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+struct Match {
+    word: usize,
+    start: Position,
+    direction: Direction,
+    len: usize,
+}
+
+impl Match {
+    fn new(word: usize, start: Position, direction: Direction, len: usize) -> Self {
+        Match {
+            word,
+            start,
+            direction,
+            len,
+        }
+    }
+
+    /// Returns the positions of the letters of the match, in reading order
+    fn positions(self) -> impl Iterator<Item = Position> {
+        std::iter::successors(Some(self.start), move |pos| Some(pos.step(self.direction)))
+            .take(self.len)
+    }
+}
+
+/// Prefix tree of the searched words, so that words sharing a prefix are walked once
+#[derive(Debug)]
+struct Trie {
+    nodes: Vec<TrieNode>,
+}
+
+#[derive(Debug, Default)]
+struct TrieNode {
+    children: HashMap<char, usize>,
+    words: Vec<usize>,
+}
+
+impl Trie {
+    /// Builds the tree of the given words, each node ending a word holding its index
+    fn new(words: &[&str]) -> Self {
+        let mut trie = Trie {
+            nodes: vec![TrieNode::default()],
+        };
+
+        for (index, word) in words.iter().enumerate() {
+            let mut node = 0;
+
+            for c in word.chars() {
+                node = if let Some(&child) = trie.nodes[node].children.get(&c) {
+                    child
+                } else {
+                    trie.nodes.push(TrieNode::default());
+                    let child = trie.nodes.len() - 1;
+                    trie.nodes[node].children.insert(c, child);
+                    child
+                };
+            }
+
+            trie.nodes[node].words.push(index);
+        }
+
+        trie
+    }
+
+    /// Returns the node reached from `node` with the letter `c`
+    fn child(&self, node: usize, c: char) -> Option<usize> {
+        self.nodes[node].children.get(&c).copied()
+    }
+
+    /// Returns the indices of the words ending at `node`
+    fn words(&self, node: usize) -> &[usize] {
+        &self.nodes[node].words
+    }
 }
 
 impl Grid {
@@ -28,7 +204,50 @@ impl Grid {
             width,
             height,
             cells,
+            toroidal: false,
+        }
+    }
+
+    /// Sets whether the grid wraps around its edges like a torus and returns it
+    fn toroidal(mut self, toroidal: bool) -> Self {
+        self.toroidal = toroidal;
+        self
+    }
+
+    /// Parses a grid from its rows, checking they all have the same number of letters
+    /// Both LF and CRLF line endings are accepted, as well as trailing blank lines
+    fn parse(input: &str) -> Result<Self, GridError> {
+        let mut rows: Vec<&str> = input
+            .split('\n')
+            .map(|line| line.strip_suffix('\r').unwrap_or(line))
+            .collect();
+
+        while rows.last().is_some_and(|row| row.is_empty()) {
+            rows.pop();
+        }
+
+        let Some(first) = rows.first() else {
+            return Err(GridError::Empty);
+        };
+
+        let width = first.chars().count();
+        let mut cells = Vec::with_capacity(width * rows.len());
+
+        for (index, row) in rows.iter().enumerate() {
+            let len = row.chars().count();
+
+            if len != width {
+                return Err(GridError::RaggedRow {
+                    line: index + 1,
+                    expected: width,
+                    found: len,
+                });
+            }
+
+            cells.extend(row.chars());
         }
+
+        Ok(Grid::new(width as isize, rows.len() as isize, cells))
     }
 
     fn width(&self) -> isize {
@@ -40,7 +259,10 @@ impl Grid {
     }
 
     fn get_index(&self, pos: Position) -> Option<usize> {
-        if pos.x >= 0 && pos.x < self.width && pos.y >= 0 && pos.y < self.height {
+        if self.toroidal && self.width > 0 && self.height > 0 {
+            let (x, y) = (pos.x.rem_euclid(self.width), pos.y.rem_euclid(self.height));
+            Some((y * self.width + x) as usize)
+        } else if pos.x >= 0 && pos.x < self.width && pos.y >= 0 && pos.y < self.height {
             Some((pos.y * self.width + pos.x) as usize)
         } else {
             None
@@ -51,153 +273,778 @@ impl Grid {
         self.get_index(pos).and_then(|index| self.cells.get(index))
     }
 
-    // PART 1
+    /// Returns the number of distinct cells visited when stepping in `direction` before
+    /// coming back to the start, unbounded if the grid does not wrap
+    fn cycle_len(&self, direction: Direction) -> usize {
+        if !self.toroidal || self.cells.is_empty() {
+            return usize::MAX;
+        }
 
-    fn count_matches_from(&self, x: isize, y: isize) -> usize {
-        let directions: Vec<[Position; 4]> = vec![
-            [
-                Position::new(x, y),
-                Position::new(x, y - 1),
-                Position::new(x, y - 2),
-                Position::new(x, y - 3),
-            ], // UP
-            [
-                Position::new(x, y),
-                Position::new(x + 1, y - 1),
-                Position::new(x + 2, y - 2),
-                Position::new(x + 3, y - 3),
-            ], // UP RIGHT
-            [
-                Position::new(x, y),
-                Position::new(x + 1, y),
-                Position::new(x + 2, y),
-                Position::new(x + 3, y),
-            ], // RIGHT
-            [
-                Position::new(x, y),
-                Position::new(x + 1, y + 1),
-                Position::new(x + 2, y + 2),
-                Position::new(x + 3, y + 3),
-            ], // DOWN RIGHT
-            [
-                Position::new(x, y),
-                Position::new(x, y + 1),
-                Position::new(x, y + 2),
-                Position::new(x, y + 3),
-            ], // DOWN
-            [
-                Position::new(x, y),
-                Position::new(x - 1, y + 1),
-                Position::new(x - 2, y + 2),
-                Position::new(x - 3, y + 3),
-            ], // DOWN LEFT
-            [
-                Position::new(x, y),
-                Position::new(x - 1, y),
-                Position::new(x - 2, y),
-                Position::new(x - 3, y),
-            ], // LEFT
-            [
-                Position::new(x, y),
-                Position::new(x - 1, y - 1),
-                Position::new(x - 2, y - 2),
-                Position::new(x - 3, y - 3),
-            ], // UP LEFT
-        ];
+        let period = |step: isize, size: isize| (size / gcd(step.abs(), size)) as usize;
+        let (x_period, y_period) = (
+            period(direction.dx, self.width),
+            period(direction.dy, self.height),
+        );
 
-        let mut matches = 0;
+        x_period / gcd(x_period as isize, y_period as isize) as usize * y_period
+    }
 
-        'pos: for positions in &directions {
-            let mut string = String::new();
+    /// Drops the steps that do not move, and those that step to the same cells as an
+    /// earlier one, which happens on a torus when a grid dimension divides the difference
+    /// between two steps
+    fn distinct_directions(&self, directions: &[Direction]) -> Vec<Direction> {
+        let mut distinct: Vec<Direction> = vec![];
 
-            for &pos in positions {
-                if let Some(c) = self.get(pos) {
-                    string.push(*c);
+        for &direction in directions {
+            let same_cells = |other: &Direction| {
+                if self.toroidal && !self.cells.is_empty() {
+                    (direction.dx - other.dx).rem_euclid(self.width) == 0
+                        && (direction.dy - other.dy).rem_euclid(self.height) == 0
                 } else {
-                    continue 'pos;
+                    direction == *other
                 }
+            };
+
+            if !same_cells(&Direction::NONE) && !distinct.iter().any(same_cells) {
+                distinct.push(direction);
             }
+        }
+
+        distinct
+    }
 
-            if string == "XMAS" {
-                matches += 1;
+    // PART 1
+
+    /// Counts the occurrences of each word, in the same order as `words`, reading in the
+    /// given directions from every cell
+    fn search(&self, words: &[&str], directions: &[Direction]) -> Vec<usize> {
+        let trie = Trie::new(words);
+        let directions = self.distinct_directions(directions);
+        let mut counts = vec![0; words.len()];
+
+        for y in 0..self.height() {
+            for x in 0..self.width() {
+                self.search_from(&trie, Position::new(x, y), &directions, &mut |m| {
+                    counts[m.word] += 1;
+                });
+            }
+        }
+
+        counts
+    }
+
+    /// Lists every occurrence of the words, reading in the given directions from every cell
+    fn find(&self, words: &[&str], directions: &[Direction]) -> Vec<Match> {
+        let trie = Trie::new(words);
+        let directions = self.distinct_directions(directions);
+        let mut matches = vec![];
+
+        for y in 0..self.height() {
+            for x in 0..self.width() {
+                self.search_from(&trie, Position::new(x, y), &directions, &mut |m| {
+                    matches.push(m);
+                });
             }
         }
 
         matches
     }
 
+    /// Reports the occurrences of the words of the trie starting at `start`
+    /// Single letter words are the same in every direction, so they are only reported once,
+    /// with `Direction::NONE`
+    /// On a torus a walk stops before coming back to its start, so that a word never reuses
+    /// a cell and cannot match itself over and over
+    fn search_from(
+        &self,
+        trie: &Trie,
+        start: Position,
+        directions: &[Direction],
+        on_match: &mut impl FnMut(Match),
+    ) {
+        let Some(first) = self.get(start).and_then(|&c| trie.child(0, c)) else {
+            return;
+        };
+
+        for &word in trie.words(first) {
+            on_match(Match::new(word, start, Direction::NONE, 1));
+        }
+
+        for &direction in directions {
+            let mut node = first;
+            let mut pos = start.step(direction);
+            let mut len = 1;
+
+            let max_len = self.cycle_len(direction);
+
+            while let Some(next) = (len < max_len)
+                .then(|| self.get(pos))
+                .flatten()
+                .and_then(|&c| trie.child(node, c))
+            {
+                len += 1;
+
+                for &word in trie.words(next) {
+                    on_match(Match::new(word, start, direction, len));
+                }
+
+                node = next;
+                pos = pos.step(direction);
+            }
+        }
+    }
+
+    /// Renders the grid with every letter outside of the matches replaced by `.`
+    fn render(&self, matches: &[Match]) -> String {
+        let mut kept = vec![false; self.cells.len()];
+
+        for m in matches {
+            for pos in m.positions() {
+                if let Some(index) = self.get_index(pos) {
+                    kept[index] = true;
+                }
+            }
+        }
+
+        let mut rendered = String::new();
+
+        for (index, c) in self.cells.iter().enumerate() {
+            rendered.push(if kept[index] { *c } else { '.' });
+
+            if (index + 1) % self.width as usize == 0 {
+                rendered.push('\n');
+            }
+        }
+
+        rendered
+    }
+
     // PART 2
 
-    fn matches_from(&self, x: isize, y: isize) -> bool {
-        let Some(up_r) = self.get(Position::new(x + 1, y - 1)) else {
-            return false;
-        };
+    /// Counts the placements of the pattern, or of any of its variants, in the grid
+    fn count_pattern(&self, pattern: &Pattern, options: PatternOptions) -> usize {
+        let variants = pattern.variants(options);
+        let mut matches = 0;
 
-        let Some(do_r) = self.get(Position::new(x + 1, y + 1)) else {
-            return false;
-        };
+        for y in 0..self.height() {
+            for x in 0..self.width() {
+                matches += variants
+                    .iter()
+                    .filter(|variant| variant.matches_at(self, Position::new(x, y)))
+                    .count();
+            }
+        }
 
-        let Some(do_l) = self.get(Position::new(x - 1, y + 1)) else {
-            return false;
-        };
+        matches
+    }
+}
 
-        let Some(up_l) = self.get(Position::new(x - 1, y - 1)) else {
-            return false;
-        };
+/// Which transformed copies of a pattern are searched along with it
+#[derive(Copy, Clone, Debug, Default)]
+struct PatternOptions {
+    rotations: bool,
+    reflections: bool,
+}
+
+/// Small 2D template of letters, `None` cells matching any letter
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct Pattern {
+    width: usize,
+    height: usize,
+    cells: Vec<Option<char>>,
+}
+
+impl Pattern {
+    /// Wildcard letter of parsed patterns
+    const WILDCARD: char = '.';
+
+    /// Creates a pattern from its rows, `.` standing for any letter
+    fn new(rows: &[&str]) -> Self {
+        let width = rows.first().map_or(0, |row| row.chars().count());
+
+        assert!(
+            rows.iter().all(|row| row.chars().count() == width),
+            "Invalid pattern : rows of different lengths"
+        );
+
+        let cells = rows
+            .iter()
+            .flat_map(|row| row.chars())
+            .map(|c| (c != Self::WILDCARD).then_some(c))
+            .collect();
+
+        Pattern {
+            width,
+            height: rows.len(),
+            cells,
+        }
+    }
+
+    /// The X-MAS shape: two diagonal `MAS` crossing on their `A`
+    fn x_mas() -> Self {
+        Pattern::new(&["M.S", ".A.", "M.S"])
+    }
+
+    fn get(&self, x: usize, y: usize) -> Option<char> {
+        self.cells[y * self.width + x]
+    }
+
+    /// Returns the pattern rotated 90 degrees clockwise
+    fn rotate(&self) -> Self {
+        let cells = (0..self.width)
+            .flat_map(|y| (0..self.height).map(move |x| (x, y)))
+            .map(|(x, y)| self.get(y, self.height - 1 - x))
+            .collect();
+
+        Pattern {
+            width: self.height,
+            height: self.width,
+            cells,
+        }
+    }
+
+    /// Returns the pattern mirrored left to right
+    fn reflect(&self) -> Self {
+        let cells = (0..self.height)
+            .flat_map(|y| (0..self.width).map(move |x| (x, y)))
+            .map(|(x, y)| self.get(self.width - 1 - x, y))
+            .collect();
+
+        Pattern {
+            width: self.width,
+            height: self.height,
+            cells,
+        }
+    }
+
+    /// Returns the distinct variants of the pattern allowed by the options, itself first
+    /// Symmetric patterns have identical variants, which are only kept once so that a
+    /// placement is never counted twice
+    fn variants(&self, options: PatternOptions) -> Vec<Pattern> {
+        let mut bases = vec![self.clone()];
 
-        Self::chars_matches(*up_r, *do_l) && Self::chars_matches(*do_r, *up_l)
+        if options.reflections {
+            bases.push(self.reflect());
+        }
+
+        let mut variants: Vec<Pattern> = vec![];
+
+        for base in bases {
+            let mut variant = base;
+
+            for _ in 0..if options.rotations { 4 } else { 1 } {
+                let next = variant.rotate();
+
+                if !variants.contains(&variant) {
+                    variants.push(variant);
+                }
+
+                variant = next;
+            }
+        }
+
+        variants
     }
 
-    fn chars_matches(l: char, r: char) -> bool {
-        (l == 'M' && r == 'S') || (l == 'S' && r == 'M')
+    /// Checks if the pattern matches the grid with its top left corner at `origin`
+    fn matches_at(&self, grid: &Grid, origin: Position) -> bool {
+        (0..self.height).all(|y| {
+            (0..self.width).all(|x| {
+                let pos = Position::new(origin.x + x as isize, origin.y + y as isize);
+
+                match (self.get(x, y), grid.get(pos)) {
+                    (_, None) => false,
+                    (None, Some(_)) => true,
+                    (Some(expected), Some(&c)) => expected == c,
+                }
+            })
+        })
     }
 }
 
-pub(crate) fn day_4_1() {
-    let lines = include_str!("../data/day4.txt").lines();
+/// Byte backed grid for large inputs
+/// Every orientation is scanned as contiguous row slices, without any allocation per cell
+#[derive(Debug)]
+struct ByteGrid {
+    width: usize,
+    height: usize,
+    bytes: Vec<u8>,
+}
+
+impl ByteGrid {
+    fn new(width: usize, height: usize, bytes: Vec<u8>) -> Self {
+        ByteGrid {
+            width,
+            height,
+            bytes,
+        }
+    }
+
+    /// Converts a grid of ASCII letters, returning `None` if any letter is not ASCII
+    fn from_grid(grid: &Grid) -> Option<Self> {
+        let bytes = grid
+            .cells
+            .iter()
+            .map(|&c| u8::try_from(c).ok().filter(u8::is_ascii))
+            .collect::<Option<Vec<u8>>>()?;
+
+        Some(ByteGrid::new(
+            grid.width as usize,
+            grid.height as usize,
+            bytes,
+        ))
+    }
+
+    /// Counts the occurrences of `word` in the eight directions, as `Grid::search` does,
+    /// splitting the rows between `threads` threads
+    fn count(&self, word: &[u8], threads: usize) -> usize {
+        if word.is_empty() || self.bytes.is_empty() {
+            return 0;
+        }
+
+        // Single letters are the same in every direction
+        if word.len() == 1 {
+            return self.bytes.iter().filter(|&&b| b == word[0]).count();
+        }
 
-    let width = lines.clone().next().unwrap().len() as isize;
-    let height = lines.clone().count() as isize;
-    let cells = lines.collect::<Vec<&str>>().join("").chars().collect();
+        let reversed: Vec<u8> = word.iter().rev().copied().collect();
+        let chunk = self.height.div_ceil(threads.max(1));
 
-    let grid = Grid::new(width, height, cells);
-    let mut matches: usize = 0;
+        thread::scope(|scope| {
+            let handles: Vec<_> = (0..self.height)
+                .step_by(chunk)
+                .map(|start| {
+                    let rows = start..(start + chunk).min(self.height);
+                    let reversed = &reversed;
+                    scope.spawn(move || {
+                        self.count_rows(word, rows.clone()) + self.count_rows(reversed, rows)
+                    })
+                })
+                .collect();
 
-    for y in 0..grid.height() {
-        for x in 0..grid.width() {
-            let pos = Position::new(x, y);
-            if let Some(c) = grid.get(pos) {
-                if *c == 'X' {
-                    matches += grid.count_matches_from(x, y);
+            handles.into_iter().map(|h| h.join().unwrap()).sum()
+        })
+    }
+
+    /// Counts the occurrences of `word` starting in `rows` and read rightwards, downwards,
+    /// down right or down left, the other four directions being those of the reversed word
+    fn count_rows(&self, word: &[u8], rows: Range<usize>) -> usize {
+        let len = word.len();
+        let mut alive = vec![false; self.width];
+        let mut matches = 0;
+
+        for y in rows {
+            let row = &self.bytes[y * self.width..(y + 1) * self.width];
+
+            matches += row.windows(len).filter(|w| *w == word).count();
+
+            if y + len <= self.height {
+                for dx in [0, 1, -1] {
+                    matches += self.count_down(word, y, dx, &mut alive);
                 }
             }
         }
+
+        matches
     }
 
-    println!("Number of matches = {matches}");
+    /// Counts the occurrences of `word` starting on row `y` and going down, moving by `dx`
+    /// columns at each row
+    /// Rather than reading each candidate with a stride of a whole row, every start of the
+    /// row is checked at once against the following rows, which keeps reads contiguous
+    fn count_down(&self, word: &[u8], y: usize, dx: isize, alive: &mut [bool]) -> usize {
+        let row = |k: usize| &self.bytes[(y + k) * self.width..(y + k + 1) * self.width];
+
+        alive
+            .iter_mut()
+            .zip(row(0))
+            .for_each(|(a, &b)| *a = b == word[0]);
+
+        for (k, &letter) in word.iter().enumerate().skip(1) {
+            if k >= self.width && dx != 0 {
+                return 0;
+            }
+
+            let next = row(k);
+
+            match dx {
+                0 => alive
+                    .iter_mut()
+                    .zip(next)
+                    .for_each(|(a, &b)| *a &= b == letter),
+                1 => {
+                    alive[..self.width - k]
+                        .iter_mut()
+                        .zip(&next[k..])
+                        .for_each(|(a, &b)| *a &= b == letter);
+                    alive[self.width - k..].fill(false);
+                }
+                _ => {
+                    alive[k..]
+                        .iter_mut()
+                        .zip(&next[..self.width - k])
+                        .for_each(|(a, &b)| *a &= b == letter);
+                    alive[..k].fill(false);
+                }
+            }
+        }
+
+        alive.iter().filter(|&&a| a).count()
+    }
 }
 
-pub(crate) fn day_4_2() {
-    let lines = include_str!("../data/day4.txt").lines();
-    let width = lines.clone().next().unwrap().len() as isize;
-    let height = lines.clone().count() as isize;
-
-    println!("w = {width}, h = {height}");
-
-    let cells = lines.collect::<Vec<&str>>().join("").chars().collect();
-    let grid = Grid::new(width, height, cells);
-    let mut matches: usize = 0;
-
-    for y in 0..grid.height() {
-        for x in 0..grid.width() {
-            let pos = Position::new(x, y);
-            if let Some(c) = grid.get(pos) {
-                if *c == 'A' && grid.matches_from(x, y) {
-                    matches += 1;
+impl fmt::Display for Grid {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for row in self.cells.chunks(self.width.max(1) as usize) {
+            writeln!(f, "{}", row.iter().collect::<String>())?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Options of the word search generator
+#[derive(Debug, Clone)]
+struct GeneratorOptions {
+    width: usize,
+    height: usize,
+    directions: Vec<Direction>,
+    overlaps: bool,
+    alphabet: Vec<char>,
+    seed: u64,
+}
+
+impl Default for GeneratorOptions {
+    /// A 12×12 grid of capital letters, words in the eight directions without overlaps
+    fn default() -> Self {
+        GeneratorOptions {
+            width: 12,
+            height: 12,
+            directions: Direction::ALL.to_vec(),
+            overlaps: false,
+            alphabet: ('A'..='Z').collect(),
+            seed: 0x2024_0044,
+        }
+    }
+}
+
+/// Generated word search: the filled grid and where each word was placed
+#[derive(Debug)]
+struct Puzzle {
+    grid: Grid,
+    answers: Vec<Match>,
+}
+
+/// Number of random spots tried for a word before restarting the layout
+const PLACEMENT_TRIES: usize = 200;
+/// Number of layouts tried before giving up
+const LAYOUT_TRIES: usize = 100;
+/// Number of times the free cells of accidental occurrences are drawn again before trying
+/// another layout
+const FILL_TRIES: usize = 100;
+
+/// Generates a word search holding the given words
+/// A layout places each word at a random spot, only sharing cells holding the same letter
+/// if overlaps are allowed. An occurrence lying within a single placement is intended,
+/// whether it is the placed word, its reverse reading in a palindrome or a shorter word
+/// it contains, like `MAS` in `XMAS`. Layouts forming other occurrences across placements
+/// are rejected. The free cells are then filled at random, and those forming an
+/// accidental occurrence drawn again until there is none left
+/// Returns `None` if the options are invalid or no such puzzle was found
+fn generate(words: &[&str], options: &GeneratorOptions) -> Option<Puzzle> {
+    const FREE: char = '\0';
+
+    let longest = words.iter().map(|word| word.chars().count()).max();
+
+    if options.width == 0
+        || options.height == 0
+        || options.directions.is_empty()
+        || options.alphabet.is_empty()
+        || words.iter().any(|word| word.is_empty())
+        || longest.is_some_and(|len| len > options.width.max(options.height))
+    {
+        return None;
+    }
+
+    let mut rng = Rng::new(options.seed);
+    let size = options.width * options.height;
+    let (width, height) = (options.width as isize, options.height as isize);
+
+    let mut order: Vec<usize> = (0..words.len()).collect();
+    order.sort_by_key(|&word| std::cmp::Reverse(words[word].chars().count()));
+
+    let indices_of = |m: &Match| -> Vec<usize> {
+        m.positions()
+            .map(|pos| (pos.y * width + pos.x) as usize)
+            .collect()
+    };
+
+    'layout: for _ in 0..LAYOUT_TRIES {
+        let mut cells = vec![FREE; size];
+        let mut placements: Vec<Match> = vec![];
+
+        for &word in &order {
+            let letters: Vec<char> = words[word].chars().collect();
+            let mut placed = false;
+
+            for _ in 0..PLACEMENT_TRIES {
+                let start = Position::new(
+                    rng.below(options.width) as isize,
+                    rng.below(options.height) as isize,
+                );
+                let direction = options.directions[rng.below(options.directions.len())];
+                let spot = Match::new(word, start, direction, letters.len());
+
+                let indices: Option<Vec<usize>> = spot
+                    .positions()
+                    .zip(&letters)
+                    .map(|(pos, &c)| {
+                        let inside = pos.x >= 0 && pos.x < width && pos.y >= 0 && pos.y < height;
+                        let index = (pos.y * width + pos.x) as usize;
+
+                        (inside && (cells[index] == FREE || options.overlaps && cells[index] == c))
+                            .then_some(index)
+                    })
+                    .collect();
+
+                if let Some(indices) = indices {
+                    for (index, c) in indices.into_iter().zip(&letters) {
+                        cells[index] = *c;
+                    }
+
+                    placements.push(spot);
+                    placed = true;
+                    break;
                 }
             }
+
+            if !placed {
+                continue 'layout;
+            }
+        }
+
+        let placed: Vec<Vec<usize>> = placements.iter().map(indices_of).collect();
+        let intended = |m: &Match| {
+            let run = indices_of(m);
+
+            placed.iter().any(|indices| {
+                indices
+                    .windows(run.len())
+                    .any(|w| w == run || w.iter().rev().eq(&run))
+            })
+        };
+
+        let layout = Grid::new(width, height, cells.clone());
+
+        if !layout.find(words, &options.directions).iter().all(intended) {
+            continue;
+        }
+
+        // Every occurrence lying on placed letters only is intended, so the others all
+        // cross a free cell
+        let free: Vec<usize> = (0..size).filter(|&index| cells[index] == FREE).collect();
+        let mut grid = Grid::new(width, height, cells);
+        let mut redraw = free.clone();
+
+        for _ in 0..FILL_TRIES {
+            for &index in &redraw {
+                grid.cells[index] = options.alphabet[rng.below(options.alphabet.len())];
+            }
+
+            redraw = grid
+                .find(words, &options.directions)
+                .iter()
+                .filter(|m| !intended(m))
+                .flat_map(indices_of)
+                .filter(|index| free.binary_search(index).is_ok())
+                .collect();
+
+            if redraw.is_empty() {
+                return Some(Puzzle {
+                    grid,
+                    answers: placements,
+                });
+            }
+
+            redraw.sort_unstable();
+            redraw.dedup();
         }
     }
 
+    None
+}
+
+/// Greatest common divisor, `gcd(0, n)` being `n`
+fn gcd(a: isize, b: isize) -> isize {
+    if b == 0 {
+        a
+    } else {
+        gcd(b, a % b)
+    }
+}
+
+pub(crate) fn day_4_1() {
+    let grid = Grid::parse(include_str!("../data/day4.txt"))
+        .unwrap_or_else(|e| panic!("Invalid grid : {e}"));
+
+    let matches = ByteGrid::from_grid(&grid).map_or_else(
+        || grid.search(&["XMAS"], &Direction::ALL)[0],
+        |byte_grid| byte_grid.count(b"XMAS", 1),
+    );
+
     println!("Number of matches = {matches}");
 }
+
+pub(crate) fn day_4_2() {
+    let grid = Grid::parse(include_str!("../data/day4.txt"))
+        .unwrap_or_else(|e| panic!("Invalid grid : {e}"));
+
+    println!("w = {}, h = {}", grid.width(), grid.height());
+
+    let matches = grid.count_pattern(
+        &Pattern::x_mas(),
+        PatternOptions {
+            rotations: true,
+            reflections: true,
+        },
+    );
+
+    println!("Number of matches = {matches}");
+}
+
+pub(crate) fn day_4_words() {
+    let mut toroidal = false;
+    let mut steps: Vec<Direction> = vec![];
+    let mut words: Vec<String> = vec![];
+    let mut args = std::env::args().skip(1);
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--torus" => toroidal = true,
+            "--knight" => steps.extend(Direction::KNIGHT),
+            "--step" => steps.push(Direction::parse(&args.next().unwrap())),
+            _ if arg.starts_with("--") => (),
+            _ => words.push(arg),
+        }
+    }
+
+    let grid = Grid::parse(include_str!("../data/day4.txt"))
+        .unwrap_or_else(|e| panic!("Invalid grid : {e}"))
+        .toroidal(toroidal);
+    let words: Vec<&str> = if words.is_empty() {
+        vec!["XMAS", "XMA", "SAM", "MAS"]
+    } else {
+        words.iter().map(String::as_str).collect()
+    };
+    let steps = if steps.is_empty() {
+        Direction::ALL.to_vec()
+    } else {
+        steps
+    };
+
+    let matches = grid.find(&words, &steps);
+
+    if std::env::args().any(|arg| arg == "--render") {
+        print!("{}", grid.render(&matches));
+    }
+
+    if std::env::args().any(|arg| arg == "--list") {
+        for m in &matches {
+            println!(
+                "{} at ({}, {}) towards ({}, {})",
+                words[m.word], m.start.x, m.start.y, m.direction.dx, m.direction.dy
+            );
+        }
+    }
+
+    for (word, count) in words.iter().zip(grid.search(&words, &steps)) {
+        println!("{word} : {count}");
+    }
+}
+
+pub(crate) fn day_4_bench() {
+    const LETTERS: &[u8] = b"XMAS";
+
+    let size = std::env::args()
+        .skip_while(|arg| arg != "--size")
+        .nth(1)
+        .map_or(10_000, |s| s.parse::<usize>().unwrap());
+    let threads = thread::available_parallelism().map_or(1, usize::from);
+
+    let mut rng = Rng::new(0x2024_0004);
+    let bytes: Vec<u8> = (0..size * size)
+        .map(|_| LETTERS[rng.below(LETTERS.len())])
+        .collect();
+
+    let grid = Grid::new(
+        size as isize,
+        size as isize,
+        bytes.iter().map(|&b| char::from(b)).collect(),
+    );
+    let byte_grid = ByteGrid::new(size, size, bytes);
+
+    let start = Instant::now();
+    let slow = grid.search(&["XMAS"], &Direction::ALL)[0];
+    println!("Grid search            : {slow} in {:?}", start.elapsed());
+
+    let start = Instant::now();
+    let fast = byte_grid.count(b"XMAS", 1);
+    println!("Byte grid              : {fast} in {:?}", start.elapsed());
+
+    let start = Instant::now();
+    let parallel = byte_grid.count(b"XMAS", threads);
+    println!(
+        "Byte grid, {threads} thread(s) : {parallel} in {:?}",
+        start.elapsed()
+    );
+
+    assert_eq!(slow, fast, "Byte grid disagrees with grid search");
+    assert_eq!(fast, parallel, "Parallel count disagrees");
+}
+
+pub(crate) fn day_4_generate() {
+    let mut options = GeneratorOptions::default();
+    let mut words: Vec<String> = vec![];
+    let mut args = std::env::args().skip(1);
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--size" => {
+                options.width = args.next().unwrap().parse::<usize>().unwrap();
+                options.height = options.width;
+            }
+            "--seed" => options.seed = args.next().unwrap().parse::<u64>().unwrap(),
+            "--easy" => {
+                options.directions = vec![Direction::RIGHT, Direction::DOWN, Direction::DOWN_RIGHT];
+            }
+            "--overlaps" => options.overlaps = true,
+            _ if arg.starts_with("--") => (),
+            _ => words.push(arg),
+        }
+    }
+
+    let words: Vec<&str> = if words.is_empty() {
+        vec!["XMAS", "SANTA", "ELF", "SLEIGH", "REINDEER"]
+    } else {
+        words.iter().map(String::as_str).collect()
+    };
+
+    let Some(puzzle) = generate(&words, &options) else {
+        println!("No puzzle found for these words and size");
+        return;
+    };
+
+    println!("Puzzle :");
+    print!("{}", puzzle.grid);
+
+    println!();
+    println!("Answer key :");
+    print!("{}", puzzle.grid.render(&puzzle.answers));
+
+    for m in &puzzle.answers {
+        println!(
+            "{} at ({}, {}) towards ({}, {})",
+            words[m.word], m.start.x, m.start.y, m.direction.dx, m.direction.dy
+        );
+    }
+}
@@ -1,12 +1,36 @@
+use crate::rng::Rng;
 use std::collections::{HashMap, HashSet, VecDeque};
+use std::fmt;
+use std::hash::Hash;
+use std::time::Instant;
+
+/// Error returned when sorting a graph that holds a cycle, with the nodes of one cycle
+/// in order, each having an edge to the next and the last one to the first
+#[derive(Debug, PartialEq, Eq)]
+struct CycleError<N> {
+    cycle: Vec<N>,
+}
+
+impl<N: fmt::Display> fmt::Display for CycleError<N> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let nodes: Vec<String> = self
+            .cycle
+            .iter()
+            .chain(self.cycle.first())
+            .map(ToString::to_string)
+            .collect();
+
+        write!(f, "cycle detected in graph : {}", nodes.join(" -> "))
+    }
+}
 
-/// A directed graph implementation using adjacency lists
+/// A directed graph implementation using adjacency lists, over any hashable node value
 #[derive(Debug)]
-struct DirectedGraph {
-    nodes: HashMap<i32, Node>,
+struct DirectedGraph<N: Eq + Hash + Clone> {
+    nodes: HashMap<N, Node<N>>,
 }
 
-impl DirectedGraph {
+impl<N: Eq + Hash + Clone> DirectedGraph<N> {
     /// Creates a new empty directed graph
     fn new() -> Self {
         Self {
@@ -15,14 +39,14 @@ impl DirectedGraph {
     }
 
     /// Adds a new node with the given value to the graph
-    fn add_node(&mut self, value: i32) {
-        self.nodes.insert(value, Node::new(value));
+    fn add_node(&mut self, value: N) {
+        self.nodes.insert(value, Node::new());
     }
 
     /// Adds a directed edge from one node to another
-    fn add_edge(&mut self, from: i32, to: i32) {
+    fn add_edge(&mut self, from: N, to: N) {
         if let Some(from_node) = self.nodes.get_mut(&from) {
-            from_node.add_outgoing(to);
+            from_node.add_outgoing(to.clone());
         }
         if let Some(to_node) = self.nodes.get_mut(&to) {
             to_node.add_incoming(from);
@@ -30,62 +54,114 @@ impl DirectedGraph {
     }
 
     /// Performs a topological sort of the graph using Kahn's algorithm
-    /// Returns the sorted nodes or an error if a cycle is detected
-    fn topological_sort(&mut self) -> Result<Vec<i32>, &'static str> {
+    /// Returns the sorted nodes or an error holding one of the cycles
+    /// The graph is left untouched, so it can be sorted again
+    fn topological_sort(&self) -> Result<Vec<N>, CycleError<N>> {
+        let values: Vec<N> = self.nodes.keys().cloned().collect();
+        self.sort_subgraph(&values)
+    }
+
+    /// Performs a topological sort of the subgraph made of the given nodes, ignoring edges
+    /// from or to other nodes, values missing from the graph having no edges
+    /// Values given more than once are kept once, at their first position
+    /// Kahn's algorithm runs on a copy of the incoming degrees, so that the same graph can
+    /// be queried for many subsets
+    fn sort_subgraph(&self, values: &[N]) -> Result<Vec<N>, CycleError<N>> {
         let mut result = Vec::new();
-        let total_nodes = self.nodes.len();
-
-        // Get nodes with no incoming edges to start
-        let mut no_incoming: VecDeque<i32> = self
-            .nodes
-            .values()
-            .filter(|node| node.incoming_degree() == 0)
-            .map(|node| node.value)
+
+        let mut seen: HashSet<&N> = HashSet::new();
+        let values: Vec<&N> = values.iter().filter(|value| seen.insert(value)).collect();
+
+        // Count incoming edges from within the subgraph
+        let mut in_degree: HashMap<&N, usize> = values.iter().map(|&value| (value, 0)).collect();
+
+        for &value in &values {
+            if let Some(node) = self.nodes.get(value) {
+                let degree = node
+                    .incoming
+                    .iter()
+                    .filter(|from| in_degree.contains_key(from))
+                    .count();
+                in_degree.insert(value, degree);
+            }
+        }
+
+        // Get nodes with no incoming edges to start, in the given order
+        let mut no_incoming: VecDeque<&N> = values
+            .iter()
+            .copied()
+            .filter(|value| in_degree[value] == 0)
             .collect();
+        let mut queued: HashSet<&N> = no_incoming.iter().copied().collect();
 
         // While there are nodes with no incoming edges
         while let Some(n) = no_incoming.pop_front() {
-            result.push(n);
-
-            // Get outgoing edges before modifying the graph
-            let outgoing: Vec<i32> = self
-                .nodes
-                .get(&n)
-                .map(|node| node.outgoing.iter().copied().collect())
-                .unwrap_or_default();
-
-            // Remove edges and check for new nodes with no incoming edges
-            for m in outgoing {
-                if let Some(node) = self.nodes.get_mut(&m) {
-                    node.remove_incoming(n);
-                    if node.incoming_degree() == 0 {
-                        no_incoming.push_back(m);
+            if let Some(node) = self.nodes.get(n) {
+                // Lower the degree of the successors and queue those left without incoming edges
+                for m in &node.outgoing {
+                    if let Some(degree) = in_degree.get_mut(m) {
+                        *degree -= 1;
+                        if *degree == 0 && queued.insert(m) {
+                            no_incoming.push_back(m);
+                        }
                     }
                 }
             }
+
+            result.push(n.clone());
         }
 
         // If we visited all nodes, sort succeeded, otherwise there was a cycle
-        if result.len() == total_nodes {
+        if result.len() == in_degree.len() {
             Ok(result)
         } else {
-            Err("Cycle detected in graph")
+            Err(CycleError {
+                cycle: self.find_cycle(&in_degree).unwrap_or_default(),
+            })
         }
     }
+
+    /// Finds a cycle once Kahn's algorithm stalled, given the incoming degrees it left
+    /// Every node left with incoming edges has one from another such node, so walking
+    /// those edges backwards from any of them must come back to a node already seen
+    /// Returns `None` if no node was left with incoming edges
+    fn find_cycle(&self, in_degree: &HashMap<&N, usize>) -> Option<Vec<N>> {
+        let left = |value: &N| in_degree.get(value).is_some_and(|&degree| degree > 0);
+
+        let mut current = in_degree.keys().find(|value| left(value)).copied()?;
+
+        let mut path: Vec<&N> = vec![];
+        let mut seen: HashMap<&N, usize> = HashMap::new();
+
+        while !seen.contains_key(current) {
+            seen.insert(current, path.len());
+            path.push(current);
+            current = self
+                .nodes
+                .get(current)?
+                .incoming
+                .iter()
+                .find(|from| left(from))?;
+        }
+
+        // The path was walked against the edges
+        let mut cycle: Vec<N> = path.split_off(seen[current]).into_iter().cloned().collect();
+        cycle.reverse();
+        Some(cycle)
+    }
 }
 
-impl DirectedGraph {
-    /// Creates a new directed graph from an update and set of rules
-    fn from_update_and_rules(update: &Update, rules: &[Rule]) -> Self {
+impl DirectedGraph<i32> {
+    /// Creates a new directed graph holding every rule, to be sorted for each update
+    fn from_rules(rules: &[Rule]) -> Self {
         let mut graph = DirectedGraph::new();
 
-        // Add nodes for each page
-        for &page in &update.pages {
-            graph.add_node(page);
+        for rule in rules {
+            graph.add_node(rule.left());
+            graph.add_node(rule.right());
         }
 
-        // Add edges for each applicable rule
-        for rule in rules.iter().filter(|r| r.apply_to(update)) {
+        for rule in rules {
             graph.add_edge(rule.left(), rule.right());
         }
 
@@ -93,6 +169,51 @@ impl DirectedGraph {
     }
 }
 
+/// Ordering rules indexed by pair of pages, so that checking an update costs a few
+/// lookups per page, whatever the number of rules
+#[derive(Debug)]
+struct RuleSet {
+    pairs: HashSet<(i32, i32)>,
+}
+
+impl RuleSet {
+    /// Indexes the given rules
+    fn new(rules: &[Rule]) -> Self {
+        Self {
+            pairs: rules
+                .iter()
+                .map(|rule| (rule.left(), rule.right()))
+                .collect(),
+        }
+    }
+
+    /// Checks if a rule requires `left` to come before `right`
+    fn orders(&self, left: i32, right: i32) -> bool {
+        self.pairs.contains(&(left, right))
+    }
+
+    /// Checks if the update respects every rule applying to it, as `Update::respect` does,
+    /// pages appearing twice counting at their first position
+    /// Only neighbouring pages are checked, which is enough when the rules order every pair
+    /// of pages of the update consistently, as in the puzzle input: pages out of order then
+    /// always include two neighbours out of order
+    fn respected_by(&self, update: &Update) -> bool {
+        let mut seen: HashSet<i32> = HashSet::new();
+        let mut previous: Option<i32> = None;
+
+        update
+            .pages
+            .iter()
+            .filter(|&&page| seen.insert(page))
+            .all(|&page| {
+                let respected = !self.orders(page, page)
+                    && previous.is_none_or(|previous| !self.orders(page, previous));
+                previous = Some(page);
+                respected
+            })
+    }
+}
+
 /// Represents an ordering rule between two pages
 #[derive(Debug)]
 struct Rule {
@@ -154,46 +275,29 @@ impl Update {
 
 /// Represents a node in the directed graph
 #[derive(Debug, Clone)]
-struct Node {
-    value: i32,
-    outgoing: HashSet<i32>,
-    incoming: HashSet<i32>,
+struct Node<N: Eq + Hash + Clone> {
+    outgoing: HashSet<N>,
+    incoming: HashSet<N>,
 }
 
-impl Node {
-    /// Creates a new node with the given value
-    fn new(value: i32) -> Self {
+impl<N: Eq + Hash + Clone> Node<N> {
+    /// Creates a new node without edges
+    fn new() -> Self {
         Self {
-            value,
             outgoing: HashSet::new(),
             incoming: HashSet::new(),
         }
     }
 
     /// Adds an incoming edge from the given node
-    fn add_incoming(&mut self, from_value: i32) {
+    fn add_incoming(&mut self, from_value: N) {
         self.incoming.insert(from_value);
     }
 
     /// Adds an outgoing edge to the given node
-    fn add_outgoing(&mut self, to_value: i32) {
+    fn add_outgoing(&mut self, to_value: N) {
         self.outgoing.insert(to_value);
     }
-
-    /// Removes an incoming edge from the given node
-    fn remove_incoming(&mut self, from_value: i32) {
-        self.incoming.remove(&from_value);
-    }
-
-    /// Removes an outgoing edge to the given node
-    fn remove_outgoing(&mut self, to_value: i32) {
-        self.outgoing.remove(&to_value);
-    }
-
-    /// Returns the number of incoming edges
-    fn incoming_degree(&self) -> usize {
-        self.incoming.len()
-    }
 }
 
 pub(crate) fn day_5_1() {
@@ -216,14 +320,11 @@ pub(crate) fn day_5_1() {
         .map(|s| Update::new(s.split(',').map(|s| s.parse::<i32>().unwrap()).collect()))
         .collect();
 
+    let rule_set = RuleSet::new(&rules);
+
     let total: i32 = updates
         .iter()
-        .filter(|update| {
-            rules
-                .iter()
-                .filter(|rule| rule.apply_to(update))
-                .all(|rule| update.respect(rule))
-        })
+        .filter(|update| rule_set.respected_by(update))
         .map(|update| *update.middle_el().unwrap())
         .sum();
 
@@ -250,22 +351,94 @@ pub(crate) fn day_5_2() {
         .map(|s| Update::new(s.split(',').map(|s| s.parse::<i32>().unwrap()).collect()))
         .collect();
 
+    let graph = DirectedGraph::from_rules(&rules);
+    let rule_set = RuleSet::new(&rules);
+
     let total: i32 = updates
         .into_iter()
+        .enumerate()
+        .filter(|(_, update)| !rule_set.respected_by(update))
+        .filter_map(|(index, update)| match graph.sort_subgraph(&update.pages) {
+            Ok(reordered_pages) => Some(update.set_pages(reordered_pages)),
+            Err(error) => {
+                let cycle_rules: Vec<String> = error
+                    .cycle
+                    .iter()
+                    .zip(error.cycle.iter().cycle().skip(1))
+                    .map(|(left, right)| format!("{left}|{right}"))
+                    .collect();
+
+                println!(
+                    "Update {} ({:?}) skipped, {error} formed by rules {}",
+                    index + 1,
+                    update.pages,
+                    cycle_rules.join(", ")
+                );
+
+                None
+            }
+        })
+        .map(|update| *update.middle_el().unwrap())
+        .sum();
+
+    println!("Total = {total:#?}");
+}
+
+pub(crate) fn day_5_bench() {
+    const PAGES: usize = 100;
+    const UPDATES: usize = 5_000;
+
+    let mut rng = Rng::new(0x2024_0005);
+
+    // Rules follow a hidden order of the pages and, as in the puzzle input, every pair of
+    // pages gets one, which the rule set relies on
+    let mut order: Vec<i32> = (10..).take(PAGES).collect();
+    for i in (1..PAGES).rev() {
+        order.swap(i, rng.below(i + 1));
+    }
+
+    let rules: Vec<Rule> = (0..PAGES)
+        .flat_map(|a| (a + 1..PAGES).map(move |b| (a, b)))
+        .map(|(a, b)| Rule::new(order[a], order[b]))
+        .collect();
+
+    // Half of the updates are drawn in order, the others shuffled
+    let updates: Vec<Update> = (0..UPDATES)
+        .map(|_| {
+            let len = 5 + 2 * rng.below(10);
+            let mut ranks: Vec<usize> = vec![];
+            while ranks.len() < len {
+                let rank = rng.below(PAGES);
+                if !ranks.contains(&rank) {
+                    ranks.push(rank);
+                }
+            }
+            if rng.below(2) == 0 {
+                ranks.sort_unstable();
+            }
+            Update::new(ranks.into_iter().map(|rank| order[rank]).collect())
+        })
+        .collect();
+
+    let start = Instant::now();
+    let scanned = updates
+        .iter()
         .filter(|update| {
             rules
                 .iter()
                 .filter(|rule| rule.apply_to(update))
-                .any(|rule| !update.respect(rule))
+                .all(|rule| update.respect(rule))
         })
-        .map(|update| {
-            let mut graph = DirectedGraph::from_update_and_rules(&update, &rules);
-            let reordered_pages = graph.topological_sort().expect("Invalid graph structure");
+        .count();
+    println!("Rule scan : {scanned} ordered in {:?}", start.elapsed());
 
-            update.set_pages(reordered_pages)
-        })
-        .map(|update| *update.middle_el().unwrap())
-        .sum();
+    let start = Instant::now();
+    let rule_set = RuleSet::new(&rules);
+    let indexed = updates
+        .iter()
+        .filter(|update| rule_set.respected_by(update))
+        .count();
+    println!("Rule set  : {indexed} ordered in {:?}", start.elapsed());
 
-    println!("Total = {total:#?}");
+    assert_eq!(scanned, indexed, "Rule set disagrees with the rule scan");
 }
@@ -8,26 +8,43 @@ mod day4;
 mod day5;
 mod day6;
 mod day7;
+mod rng;
 
-use day1::{day_1_1, day_1_2};
-use day2::{day_2_1, day_2_2};
-use day3::{day_3_1, day_3_2};
-use day4::{day_4_1, day_4_2};
-use day5::{day_5_1, day_5_2};
+use day1::{day_1_1, day_1_2, day_1_matrices};
+use day2::{day_2_1, day_2_2, day_2_removals, day_2_stream, day_2_windows};
+#[cfg(fuzzing)]
+use day3::day_3_fuzz;
+use day3::{day_3_1, day_3_2, day_3_bench, day_3_clean, day_3_extended, day_3_listing, day_3_scan};
+use day4::{day_4_1, day_4_2, day_4_bench, day_4_generate, day_4_words};
+use day5::{day_5_1, day_5_2, day_5_bench};
 use day6::{day_6_1, day_6_2};
 use day7::{day_7_1, day_7_2};
 
 fn main() {
     // day_1_1();
     // day_1_2();
+    // day_1_matrices();
     // day_2_1();
     // day_2_2();
+    // day_2_removals();
+    // day_2_stream();
+    // day_2_windows();
     // day_3_1();
     // day_3_2();
+    // day_3_extended();
+    // day_3_listing();
+    // day_3_clean();
+    // day_3_scan();
+    // day_3_bench();
+    // day_3_fuzz(); // needs RUSTFLAGS="--cfg fuzzing"
     // day_4_1();
     // day_4_2();
+    // day_4_words();
+    // day_4_bench();
+    // day_4_generate();
     // day_5_1();
     // day_5_2();
+    // day_5_bench();
     // day_6_1();
     // day_6_2();
     // day_7_1();
@@ -0,0 +1,24 @@
+/// Xorshift generator, enough for puzzles, benchmarks and fuzzing without a dependency
+#[derive(Debug)]
+pub(crate) struct Rng {
+    state: u64,
+}
+
+impl Rng {
+    /// Creates a generator from a seed, zero being replaced as it would only yield zeros
+    pub(crate) fn new(seed: u64) -> Self {
+        Rng { state: seed.max(1) }
+    }
+
+    pub(crate) fn next(&mut self) -> u64 {
+        self.state ^= self.state << 13;
+        self.state ^= self.state >> 7;
+        self.state ^= self.state << 17;
+        self.state
+    }
+
+    /// Returns a number in `0..n`
+    pub(crate) fn below(&mut self, n: usize) -> usize {
+        (self.next() % n as u64) as usize
+    }
+}